@@ -0,0 +1,47 @@
+#![feature(test)]
+
+extern crate test;
+
+use rocket_hawk::{MemoryNonceStore, NonceStore};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use test::Bencher;
+
+const THREADS: usize = 8;
+const NONCES: usize = 1_000;
+const RETAIN: Duration = Duration::from_secs(120);
+
+// record distinct nonces from several threads at once
+fn concurrent(b: &mut Bencher, shards: usize) {
+    let store = Arc::new(MemoryNonceStore::new().shards(shards));
+    let mut round = 0;
+    b.iter(|| {
+        round += 1;
+        let threads: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let store = store.clone();
+                thread::spawn(move || {
+                    for n in 0..NONCES {
+                        store
+                            .check_and_store(&format!("{}:{}:{}", t, round, n), RETAIN)
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    });
+}
+
+#[bench]
+fn single_lock(b: &mut Bencher) {
+    concurrent(b, 1);
+}
+
+#[bench]
+fn sharded(b: &mut Bencher) {
+    concurrent(b, 16);
+}
@@ -0,0 +1,110 @@
+use hawk::{Key, SHA256, SHA384, SHA512};
+use ring::digest;
+use std::fmt;
+use std::str::FromStr;
+
+/// The HMAC digest algorithm used with a Hawk key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HawkAlgorithm {
+    /// HMAC-SHA-256, the most common choice for Hawk.
+    Sha256,
+    /// HMAC-SHA-384.
+    Sha384,
+    /// HMAC-SHA-512.
+    Sha512,
+}
+
+impl HawkAlgorithm {
+    /// Create a Hawk key from the given secret, using this algorithm.
+    pub fn key<B: Into<Vec<u8>>>(self, secret: B) -> Key {
+        Key::new(secret, self.digest())
+    }
+
+    /// The digest algorithm underlying this HMAC algorithm.
+    pub(crate) fn digest(self) -> &'static digest::Algorithm {
+        match self {
+            HawkAlgorithm::Sha256 => &SHA256,
+            HawkAlgorithm::Sha384 => &SHA384,
+            HawkAlgorithm::Sha512 => &SHA512,
+        }
+    }
+
+    /// The length, in bytes, of MACs produced with this algorithm.
+    pub fn mac_len(self) -> usize {
+        match self {
+            HawkAlgorithm::Sha256 => 32,
+            HawkAlgorithm::Sha384 => 48,
+            HawkAlgorithm::Sha512 => 64,
+        }
+    }
+
+    /// Determine the algorithm that produced a MAC of the given length, in bytes.
+    pub(crate) fn from_mac_len(len: usize) -> Option<Self> {
+        match len {
+            32 => Some(HawkAlgorithm::Sha256),
+            48 => Some(HawkAlgorithm::Sha384),
+            64 => Some(HawkAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HawkAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            HawkAlgorithm::Sha256 => "sha256",
+            HawkAlgorithm::Sha384 => "sha384",
+            HawkAlgorithm::Sha512 => "sha512",
+        })
+    }
+}
+
+/// Algorithms are parsed case-insensitively from names like `sha256` or `SHA-256`.
+impl FromStr for HawkAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_ascii_lowercase().replace('-', "")[..] {
+            "sha256" => Ok(HawkAlgorithm::Sha256),
+            "sha384" => Ok(HawkAlgorithm::Sha384),
+            "sha512" => Ok(HawkAlgorithm::Sha512),
+            _ => Err(format!("Unknown Hawk algorithm {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HawkAlgorithm;
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("sha256".parse(), Ok(HawkAlgorithm::Sha256));
+        assert_eq!("SHA-384".parse(), Ok(HawkAlgorithm::Sha384));
+        assert_eq!("Sha512".parse(), Ok(HawkAlgorithm::Sha512));
+        assert!("md5".parse::<HawkAlgorithm>().is_err());
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        for alg in &[
+            HawkAlgorithm::Sha256,
+            HawkAlgorithm::Sha384,
+            HawkAlgorithm::Sha512,
+        ] {
+            assert_eq!(alg.to_string().parse(), Ok(*alg));
+        }
+    }
+
+    #[test]
+    fn test_mac_len() {
+        for alg in &[
+            HawkAlgorithm::Sha256,
+            HawkAlgorithm::Sha384,
+            HawkAlgorithm::Sha512,
+        ] {
+            assert_eq!(alg.key(vec![1u8; 32]).sign(b"data").len(), alg.mac_len());
+            assert_eq!(HawkAlgorithm::from_mac_len(alg.mac_len()), Some(*alg));
+        }
+    }
+}
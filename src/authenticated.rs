@@ -0,0 +1,1106 @@
+use super::validator::validate_request;
+use super::{
+    DefaultPolicy, HawkAlgorithm, HawkCredentials, HawkError, HawkField, HawkPolicy, HawkPrincipal,
+    HawkRequestInfo, HawkValidator,
+};
+use hawk::mac::{Mac, MacType};
+use hawk::{Header, Key};
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request, State};
+use rocket::Outcome;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use time::Duration;
+
+/// A request guard to require an "Authorization" header containing a Hawk value with a valid
+/// MAC for this request.  The key for the header's id is looked up in the `HawkCredentials`
+/// managed by the Rocket instance.
+///
+/// This validates the MAC and the timestamp (see `HawkConfig::ts_skew`), as well as the `app`
+/// and `dlg` attributes if validators for them are configured, and the nonce if a
+/// `HawkConfig::nonce_store` is set.  The payload hash is checked only by the
+/// `HawkVerifiedBody` data guard.
+///
+/// This dereferences to the `HawkPrincipal` for the authenticated caller.
+///
+/// The policy `P` can tighten or relax verification for particular routes; see `HawkPolicy`.
+///
+/// Successful verification is performed only once per request, no matter how many guards or
+/// policies use it, and the nonce is checked at most once.
+pub struct HawkAuthenticated<P: HawkPolicy = DefaultPolicy>(HawkPrincipal, PhantomData<P>);
+
+impl<P: HawkPolicy> HawkAuthenticated<P> {
+    /// Get the principal, consuming the guard.
+    pub fn into_inner(self) -> HawkPrincipal {
+        self.0
+    }
+
+    fn new(principal: HawkPrincipal) -> Self {
+        HawkAuthenticated(principal, PhantomData)
+    }
+}
+
+impl<'a, 'r, P: HawkPolicy> FromRequest<'a, 'r> for HawkAuthenticated<P> {
+    type Error = HawkError;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let credentials = match request.guard::<State<HawkCredentials>>() {
+            Outcome::Success(credentials) => credentials,
+            _ => return Outcome::Failure((Status::InternalServerError, HawkError::NoCredentials)),
+        };
+        validate_request::<_, P>(request, credentials.inner()).map(HawkAuthenticated::new)
+    }
+}
+
+impl<P: HawkPolicy> Deref for HawkAuthenticated<P> {
+    type Target = HawkPrincipal;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<P: HawkPolicy> fmt::Debug for HawkAuthenticated<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("HawkAuthenticated").field(&self.0).finish()
+    }
+}
+
+/// HawkCredentials validates headers by checking their MAC against the key for the header's id,
+/// as used by the `HawkAuthenticated` guard.
+impl HawkValidator for HawkCredentials {
+    type Principal = HawkPrincipal;
+
+    fn validate(
+        &self,
+        header: &Header,
+        request: &HawkRequestInfo,
+    ) -> Result<Self::Principal, (Status, HawkError)> {
+        let id = match header.id {
+            Some(ref id) => id,
+            None => return Err((Status::Unauthorized, HawkError::UnknownId)),
+        };
+        // revocations are checked before the MAC, so that they take effect however the
+        // credentials are provided or cached
+        if self.revocations().is_id_revoked(id) {
+            return Err((Status::Unauthorized, HawkError::Revoked));
+        }
+        let candidates = match self.get_all(id, request) {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                rocket::logger::error(&format!("Could not look up Hawk credentials: {}", e));
+                return Err((Status::ServiceUnavailable, HawkError::ProviderUnavailable));
+            }
+        };
+        if candidates.is_empty() {
+            return Err((Status::Unauthorized, HawkError::UnknownId));
+        }
+        let candidates: Vec<_> = candidates
+            .into_iter()
+            .filter(|c| !self.revocations().is_key_revoked(c))
+            .collect();
+        if candidates.is_empty() {
+            return Err((Status::Unauthorized, HawkError::Revoked));
+        }
+
+        let mac_len = header.mac.as_ref().map(|m| m.len()).unwrap_or(0);
+        let mut verified = None;
+        for credential in candidates {
+            // the MAC was produced with the key, so its length identifies the algorithm; if the
+            // credential specifies an algorithm, the two must agree
+            let algorithm = match credential.algorithm {
+                Some(algorithm) if algorithm.mac_len() == mac_len => algorithm,
+                Some(_) => continue,
+                None => match HawkAlgorithm::from_mac_len(mac_len) {
+                    Some(algorithm) => algorithm,
+                    None => continue,
+                },
+            };
+
+            if mac_matches(header, &credential.key, request) {
+                verified = Some((credential, algorithm));
+                break;
+            }
+        }
+        let (credential, algorithm) = match verified {
+            Some(verified) => verified,
+            None => return Err((Status::Unauthorized, HawkError::BadMac)),
+        };
+
+        // the credential's policy is checked only after the MAC is verified, so that it is not
+        // revealed to unauthenticated callers
+        if let Some(ref allowed) = credential.allowed_algorithms {
+            if !allowed.contains(&algorithm) {
+                return Err((Status::Unauthorized, HawkError::AlgorithmNotAllowed));
+            }
+        }
+        if let Some(ref allowed) = credential.allowed_methods {
+            if !allowed.iter().any(|m| m.as_str() == request.method()) {
+                return Err((Status::Forbidden, HawkError::MethodNotAllowed));
+            }
+        }
+        if credential.require_hash && header.hash.is_none() {
+            return Err((Status::BadRequest, HawkError::MissingField(HawkField::Hash)));
+        }
+
+        let app = header.app.as_ref().map(|a| &a[..]);
+        let dlg = header.dlg.as_ref().map(|d| &d[..]);
+        if let Some(ref app_validator) = self.app_validator {
+            if !app_validator(id, app) {
+                return Err((Status::Unauthorized, HawkError::BadApp));
+            }
+        }
+        if let Some(ref dlg_validator) = self.dlg_validator {
+            if !dlg_validator(id, app, dlg) {
+                return Err((Status::Unauthorized, HawkError::BadDlg));
+            }
+        }
+
+        Ok(HawkPrincipal::new(
+            id.clone(),
+            algorithm,
+            credential,
+            header.clone(),
+        ))
+    }
+
+    fn ts_skew(&self, principal: &HawkPrincipal) -> Option<Duration> {
+        principal.ts_skew
+    }
+
+    fn check_nonce(&self, principal: &HawkPrincipal) -> bool {
+        principal.check_nonce
+    }
+}
+
+// Check the header's MAC with the given key.  This does not use
+// `hawk::Request::validate_header`, as that also checks the timestamp, and overflows on
+// timestamps far from now; the caller checks the timestamp against the skew instead.
+fn mac_matches(header: &Header, key: &Key, request: &HawkRequestInfo) -> bool {
+    let (ts, nonce, mac) = match (header.ts, &header.nonce, &header.mac) {
+        (Some(ts), Some(nonce), Some(mac)) => (ts, nonce, mac),
+        _ => return false,
+    };
+    let calculated = Mac::new(
+        MacType::Header,
+        key,
+        ts,
+        nonce,
+        request.method(),
+        request.host(),
+        request.port(),
+        request.path(),
+        header.hash.as_deref(),
+        header.ext.as_deref(),
+    );
+    match calculated {
+        // Mac's comparison is constant-time
+        Ok(calculated) => &calculated == mac,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HawkAuthenticated;
+    use crate::{
+        CredentialsProvider, HawkAlgorithm, HawkConfig, HawkCredential, HawkCredentials, HawkError,
+        Lenient, MapProvider, MemoryNonceStore, ProviderError, ReplayAttempt, ReplayKey, Strict,
+        TenantProvider,
+    };
+    use hawk::{Credentials, Key, RequestBuilder, SHA256};
+    use rocket::config::{Config, Environment, Table, Value};
+    use rocket::error::LaunchErrorKind;
+    use rocket::http::{Header, Method, Status};
+    use rocket::local::Client;
+    use rocket::response::status;
+    use rocket::Rocket;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use time::{Duration, Timespec};
+
+    const KEY: [u8; 32] = [99u8; 32];
+
+    #[get("/")]
+    fn index(hawk: Result<HawkAuthenticated, HawkError>) -> status::Custom<String> {
+        match hawk {
+            Ok(h) => status::Custom(Status::Ok, h.id().to_string()),
+            Err(e) => status::Custom(Status::Unauthorized, format!("{:?}", e)),
+        }
+    }
+
+    fn rocket() -> Rocket {
+        let credentials = HawkCredentials::new().add("me", Key::new(KEY.to_vec(), &SHA256));
+        rocket::ignite()
+            .manage(credentials)
+            .mount("/", routes![index])
+    }
+
+    // make a Hawk header for the given id, key, and request details
+    fn make_header(id: &str, key: &[u8], method: &str, port: u16, path: &str) -> Header<'static> {
+        make_header_at(id, key, method, port, path, time::now().to_timespec())
+    }
+
+    // like make_header, but with a specific timestamp
+    fn make_header_at(
+        id: &str,
+        key: &[u8],
+        method: &str,
+        port: u16,
+        path: &str,
+        ts: Timespec,
+    ) -> Header<'static> {
+        let credentials = Credentials {
+            id: id.to_string(),
+            key: Key::new(key.to_vec(), &SHA256),
+        };
+        let header = RequestBuilder::new(method, "localhost", port, path)
+            .request()
+            .make_header_full(&credentials, ts, "nonce")
+            .unwrap();
+        Header::new("Authorization", format!("Hawk {}", header))
+    }
+
+    fn check(rocket: Rocket, auth: Option<Header<'static>>, expected: (Status, &str)) {
+        let client = Client::new(rocket).unwrap();
+        let mut req = client
+            .get("/")
+            .header(Header::new("Host", "localhost:8000"));
+        if let Some(auth) = auth {
+            req = req.header(auth);
+        }
+        let mut res = req.dispatch();
+        assert_eq!(
+            (res.status(), res.body_string()),
+            (expected.0, Some(expected.1.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_valid() {
+        let auth = make_header("me", &KEY, "GET", 8000, "/");
+        check(rocket(), Some(auth), (Status::Ok, "me"));
+    }
+
+    #[test]
+    fn test_provider() {
+        // a provider that knows every id, with a key derived from the id's length
+        struct LengthProvider;
+
+        impl CredentialsProvider for LengthProvider {
+            fn lookup(&self, id: &str) -> Result<Option<HawkCredential>, ProviderError> {
+                let key = vec![id.len() as u8; 32];
+                Ok(Some(HawkCredential::new(key, HawkAlgorithm::Sha256)))
+            }
+        }
+
+        let credentials = HawkCredentials::new()
+            .add("me", Key::new(KEY.to_vec(), &SHA256))
+            .provider(LengthProvider);
+        let rocket = || {
+            rocket::ignite()
+                .manage(HawkCredentials::new().provider(LengthProvider))
+                .mount("/", routes![index])
+        };
+        let auth = make_header("abc", &[3u8; 32], "GET", 8000, "/");
+        check(rocket(), Some(auth), (Status::Ok, "abc"));
+        let auth = make_header("abc", &KEY, "GET", 8000, "/");
+        check(rocket(), Some(auth), (Status::Unauthorized, "BadMac"));
+
+        // credentials added directly take precedence over the provider
+        let rocket = rocket::ignite()
+            .manage(credentials)
+            .mount("/", routes![index]);
+        let auth = make_header("me", &KEY, "GET", 8000, "/");
+        check(rocket, Some(auth), (Status::Ok, "me"));
+    }
+
+    #[test]
+    fn test_credential_policy() {
+        let rocket = |credential: HawkCredential| {
+            rocket::ignite()
+                .manage(HawkCredentials::new().add_credential("me", credential))
+                .mount("/", routes![index])
+        };
+        let credential = || HawkCredential::from_key(Key::new(KEY.to_vec(), &SHA256));
+
+        let auth = make_header("me", &KEY, "GET", 8000, "/");
+        let allowed = credential().allow_methods(vec![Method::Get, Method::Head]);
+        check(rocket(allowed), Some(auth.clone()), (Status::Ok, "me"));
+        let post_only = credential().allow_methods(vec![Method::Post]);
+        check(
+            rocket(post_only),
+            Some(auth.clone()),
+            (Status::Unauthorized, "MethodNotAllowed"),
+        );
+
+        let sha512_only = credential().allow_algorithms(vec![HawkAlgorithm::Sha512]);
+        check(
+            rocket(sha512_only),
+            Some(auth.clone()),
+            (Status::Unauthorized, "AlgorithmNotAllowed"),
+        );
+
+        let hashed = credential().require_hash(true);
+        check(
+            rocket(hashed),
+            Some(auth),
+            (Status::Unauthorized, "MissingField(Hash)"),
+        );
+
+        // the credential's skew overrides the configured skew
+        let ts = time::now().to_timespec() - Duration::seconds(120);
+        let auth = make_header_at("me", &KEY, "GET", 8000, "/", ts);
+        let lenient = credential().ts_skew(Duration::seconds(300));
+        check(rocket(lenient), Some(auth), (Status::Ok, "me"));
+    }
+
+    #[test]
+    fn test_rotated_keys() {
+        #[get("/")]
+        fn index(hawk: HawkAuthenticated) -> String {
+            hawk.key_id().unwrap_or("none").to_string()
+        }
+
+        let provider = MapProvider::new()
+            .add_rotated(
+                "me",
+                HawkCredential::new(KEY.to_vec(), HawkAlgorithm::Sha256).key_id("old"),
+            )
+            .add_rotated(
+                "me",
+                HawkCredential::new(vec![1u8; 32], HawkAlgorithm::Sha256).key_id("new"),
+            );
+        let rocket = || {
+            rocket::ignite()
+                .manage(HawkCredentials::new().provider(provider.clone()))
+                .mount("/", routes![index])
+        };
+        let auth = make_header("me", &KEY, "GET", 8000, "/");
+        check(rocket(), Some(auth), (Status::Ok, "old"));
+        let auth = make_header("me", &[1u8; 32], "GET", 8000, "/");
+        check(rocket(), Some(auth), (Status::Ok, "new"));
+        let client = Client::new(rocket()).unwrap();
+        let res = client
+            .get("/")
+            .header(Header::new("Host", "localhost:8000"))
+            .header(make_header("me", &[2u8; 32], "GET", 8000, "/"))
+            .dispatch();
+        assert_eq!(res.status(), Status::Unauthorized);
+    }
+
+    #[test]
+    fn test_revoked() {
+        #[get("/")]
+        fn index(hawk: HawkAuthenticated) -> String {
+            hawk.key_id().unwrap_or("none").to_string()
+        }
+
+        let old = HawkCredential::new(KEY.to_vec(), HawkAlgorithm::Sha256).key_id("old");
+        let fingerprint = old.fingerprint().unwrap().to_string();
+        let provider = MapProvider::new().add_rotated("me", old).add_rotated(
+            "me",
+            HawkCredential::new(vec![1u8; 32], HawkAlgorithm::Sha256).key_id("new"),
+        );
+        let client = Client::new(
+            rocket::ignite()
+                .manage(HawkCredentials::new().provider(provider))
+                .mount("/", routes![index]),
+        )
+        .unwrap();
+        let credentials = client
+            .rocket()
+            .state::<HawkCredentials>()
+            .unwrap()
+            .revocations();
+        let status = |key: &[u8]| {
+            client
+                .get("/")
+                .header(Header::new("Host", "localhost:8000"))
+                .header(make_header("me", key, "GET", 8000, "/"))
+                .dispatch()
+                .status()
+        };
+
+        assert_eq!(status(&KEY), Status::Ok);
+
+        // revoking one key leaves the other usable
+        credentials.revoke_key(fingerprint.clone());
+        assert_eq!(status(&KEY), Status::Unauthorized);
+        assert_eq!(status(&[1u8; 32]), Status::Ok);
+
+        credentials.revoke_id("me");
+        assert_eq!(status(&[1u8; 32]), Status::Unauthorized);
+
+        credentials.replace(vec![], vec![]);
+        assert_eq!(status(&KEY), Status::Ok);
+    }
+
+    #[test]
+    fn test_provider_unavailable() {
+        // a provider whose backing service is down
+        struct DownProvider;
+
+        impl CredentialsProvider for DownProvider {
+            fn lookup(&self, _id: &str) -> Result<Option<HawkCredential>, ProviderError> {
+                Err(ProviderError("connection refused".to_string()))
+            }
+        }
+
+        let rocket = rocket::ignite()
+            .manage(HawkCredentials::new().provider(DownProvider))
+            .mount("/", routes![index]);
+        let auth = make_header("me", &KEY, "GET", 8000, "/");
+        check(
+            rocket,
+            Some(auth),
+            (Status::Unauthorized, "ProviderUnavailable"),
+        );
+
+        // the guard fails with a 503, rather than as an unknown id
+        #[get("/")]
+        fn required(hawk: HawkAuthenticated) -> String {
+            hawk.id().to_string()
+        }
+        let rocket = rocket::ignite()
+            .manage(HawkCredentials::new().provider(DownProvider))
+            .mount("/", routes![required]);
+        let client = Client::new(rocket).unwrap();
+        let res = client
+            .get("/")
+            .header(Header::new("Host", "localhost:8000"))
+            .header(make_header("me", &KEY, "GET", 8000, "/"))
+            .dispatch();
+        assert_eq!(res.status(), Status::ServiceUnavailable);
+    }
+
+    #[test]
+    fn test_closure_provider() {
+        let credentials = HawkCredentials::new().provider(|id: &str| {
+            if id == "me" {
+                Some(Key::new(KEY.to_vec(), &SHA256))
+            } else {
+                None
+            }
+        });
+        let rocket = rocket::ignite()
+            .manage(credentials)
+            .mount("/", routes![index]);
+        let auth = make_header("me", &KEY, "GET", 8000, "/");
+        check(rocket, Some(auth), (Status::Ok, "me"));
+    }
+
+    #[test]
+    fn test_tenants() {
+        let tenants = TenantProvider::new()
+            .tenant(
+                "a",
+                MapProvider::new().add_credential(
+                    "me",
+                    HawkCredential::new(KEY.to_vec(), HawkAlgorithm::Sha256),
+                ),
+            )
+            .tenant(
+                "b",
+                MapProvider::new().add_credential(
+                    "me",
+                    HawkCredential::new(vec![1u8; 32], HawkAlgorithm::Sha256),
+                ),
+            );
+        let client = Client::new(
+            rocket::ignite()
+                .manage(HawkConfig::default().tenant_header("X-Tenant"))
+                .manage(HawkCredentials::new().provider(tenants))
+                .mount("/", routes![index]),
+        )
+        .unwrap();
+        let status = |tenant: Option<&'static str>, key: &[u8]| {
+            let mut req = client
+                .get("/")
+                .header(Header::new("Host", "localhost:8000"))
+                .header(make_header("me", key, "GET", 8000, "/"));
+            if let Some(tenant) = tenant {
+                req = req.header(Header::new("X-Tenant", tenant));
+            }
+            req.dispatch().status()
+        };
+
+        assert_eq!(status(Some("a"), &KEY), Status::Ok);
+        assert_eq!(status(Some("b"), &[1u8; 32]), Status::Ok);
+        // each tenant's keys are valid only for that tenant
+        assert_eq!(status(Some("b"), &KEY), Status::Unauthorized);
+        assert_eq!(status(Some("c"), &KEY), Status::Unauthorized);
+        assert_eq!(status(None, &KEY), Status::Unauthorized);
+    }
+
+    #[test]
+    fn test_config_fairing() {
+        let config = |key: &str| {
+            let mut entry = Table::new();
+            entry.insert("key".to_string(), Value::from(key));
+            let mut credentials = Table::new();
+            credentials.insert("me".to_string(), Value::Table(entry));
+            Config::build(Environment::Development)
+                .extra("hawk_credentials", credentials)
+                .finalize()
+                .unwrap()
+        };
+
+        let rocket = rocket::custom(config(&base64::encode(KEY)))
+            .attach(HawkCredentials::fairing())
+            .mount("/", routes![index]);
+        let auth = make_header("me", &KEY, "GET", 8000, "/");
+        check(rocket, Some(auth), (Status::Ok, "me"));
+
+        // invalid credentials abort the launch
+        let rocket = rocket::custom(config("not base64")).attach(HawkCredentials::fairing());
+        match Client::new(rocket) {
+            Err(e) => match e.kind() {
+                LaunchErrorKind::FailedFairings(names) => {
+                    assert_eq!(names, &vec!["Hawk Credentials"])
+                }
+                kind => panic!("unexpected launch error {:?}", kind),
+            },
+            Ok(_) => panic!("launch did not fail"),
+        }
+    }
+
+    #[test]
+    fn test_principal() {
+        #[get("/")]
+        fn index(hawk: HawkAuthenticated) -> String {
+            let principal = hawk.into_inner();
+            format!(
+                "{} {:?} {} {}",
+                &*principal,
+                principal.algorithm(),
+                principal.ts().sec > 0,
+                principal.metadata()["role"]
+            )
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("role".to_string(), "admin".to_string());
+        let credentials = HawkCredentials::new().add_with_metadata(
+            "me",
+            Key::new(KEY.to_vec(), &SHA256),
+            metadata,
+        );
+        let rocket = rocket::ignite()
+            .manage(credentials)
+            .mount("/", routes![index]);
+        let auth = make_header("me", &KEY, "GET", 8000, "/");
+        check(rocket, Some(auth), (Status::Ok, "me Sha256 true admin"));
+    }
+
+    // make a Hawk header for "alg" using the given algorithm
+    fn make_header_alg(algorithm: HawkAlgorithm) -> Header<'static> {
+        let credentials = Credentials {
+            id: "alg".to_string(),
+            key: algorithm.key(KEY.to_vec()),
+        };
+        let header = RequestBuilder::new("GET", "localhost", 8000, "/")
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+        Header::new("Authorization", format!("Hawk {}", header))
+    }
+
+    #[test]
+    fn test_credential_algorithm() {
+        #[get("/")]
+        fn index(hawk: Result<HawkAuthenticated, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Ok(h) => status::Custom(Status::Ok, format!("{:?}", h.algorithm())),
+                Err(e) => status::Custom(Status::Unauthorized, format!("{:?}", e)),
+            }
+        }
+
+        let rocket = || {
+            let credentials = HawkCredentials::new().add_credential(
+                "alg",
+                HawkCredential::new(KEY.to_vec(), HawkAlgorithm::Sha512),
+            );
+            rocket::ignite()
+                .manage(credentials)
+                .mount("/", routes![index])
+        };
+        let auth = make_header_alg(HawkAlgorithm::Sha512);
+        check(rocket(), Some(auth), (Status::Ok, "Sha512"));
+        let auth = make_header_alg(HawkAlgorithm::Sha256);
+        check(rocket(), Some(auth), (Status::Unauthorized, "BadMac"));
+    }
+
+    #[test]
+    fn test_inferred_algorithm() {
+        #[get("/")]
+        fn index(hawk: Result<HawkAuthenticated, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Ok(h) => status::Custom(Status::Ok, format!("{:?}", h.algorithm())),
+                Err(e) => status::Custom(Status::Unauthorized, format!("{:?}", e)),
+            }
+        }
+
+        let credentials =
+            HawkCredentials::new().add("alg", HawkAlgorithm::Sha384.key(KEY.to_vec()));
+        let rocket = rocket::ignite()
+            .manage(credentials)
+            .mount("/", routes![index]);
+        let auth = make_header_alg(HawkAlgorithm::Sha384);
+        check(rocket, Some(auth), (Status::Ok, "Sha384"));
+    }
+
+    #[test]
+    fn test_verified_once() {
+        static VERIFICATIONS: AtomicUsize = AtomicUsize::new(0);
+
+        #[get("/")]
+        fn index(first: HawkAuthenticated, second: HawkAuthenticated) -> String {
+            format!("{} {}", first.id(), second.id())
+        }
+
+        // the app validator is called once for each successful MAC verification
+        let credentials = HawkCredentials::new()
+            .add("me", Key::new(KEY.to_vec(), &SHA256))
+            .app_validator(|_, _| {
+                VERIFICATIONS.fetch_add(1, Ordering::SeqCst);
+                true
+            });
+        let rocket = rocket::ignite()
+            .manage(credentials)
+            .mount("/", routes![index]);
+        let auth = make_header("me", &KEY, "GET", 8000, "/");
+        check(rocket, Some(auth), (Status::Ok, "me me"));
+        assert_eq!(VERIFICATIONS.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_verified_once_across_policies() {
+        static VERIFICATIONS: AtomicUsize = AtomicUsize::new(0);
+
+        #[get("/")]
+        fn index(lenient: HawkAuthenticated<Lenient>, default: HawkAuthenticated) -> String {
+            format!("{} {}", lenient.id(), default.id())
+        }
+
+        let credentials = HawkCredentials::new()
+            .add("me", Key::new(KEY.to_vec(), &SHA256))
+            .app_validator(|_, _| {
+                VERIFICATIONS.fetch_add(1, Ordering::SeqCst);
+                true
+            });
+        let client = Client::new(
+            rocket::ignite()
+                .manage(credentials)
+                .manage(HawkConfig::default().nonce_store(MemoryNonceStore::new()))
+                .mount("/", routes![index]),
+        )
+        .unwrap();
+        let get = |auth: Header<'static>| {
+            client
+                .get("/")
+                .header(Header::new("Host", "localhost:8000"))
+                .header(auth)
+                .dispatch()
+                .status()
+        };
+
+        // the second guard neither verifies the MAC again nor sees the nonce as replayed
+        let auth = make_header("me", &KEY, "GET", 8000, "/");
+        assert_eq!(get(auth.clone()), Status::Ok);
+        assert_eq!(VERIFICATIONS.load(Ordering::SeqCst), 1);
+        assert_eq!(get(auth), Status::Unauthorized);
+
+        // each policy's own checks still apply to the shared verification
+        let ts = time::now().to_timespec() - Duration::seconds(120);
+        let auth = make_header_at("me", &KEY, "GET", 8000, "/", ts);
+        assert_eq!(get(auth), Status::Unauthorized);
+    }
+
+    #[test]
+    fn test_no_header() {
+        check(rocket(), None, (Status::Unauthorized, "NoHeader"));
+    }
+
+    #[test]
+    fn test_unknown_id() {
+        let auth = make_header("you", &KEY, "GET", 8000, "/");
+        check(rocket(), Some(auth), (Status::Unauthorized, "UnknownId"));
+    }
+
+    #[test]
+    fn test_wrong_key() {
+        let auth = make_header("me", &[1u8; 32], "GET", 8000, "/");
+        check(rocket(), Some(auth), (Status::Unauthorized, "BadMac"));
+    }
+
+    #[test]
+    fn test_wrong_port() {
+        let auth = make_header("me", &KEY, "GET", 443, "/");
+        check(rocket(), Some(auth), (Status::Unauthorized, "BadMac"));
+    }
+
+    #[test]
+    fn test_wrong_path() {
+        let auth = make_header("me", &KEY, "GET", 8000, "/other");
+        check(rocket(), Some(auth), (Status::Unauthorized, "BadMac"));
+    }
+
+    #[test]
+    fn test_stale_timestamp() {
+        let ts = time::now().to_timespec() - Duration::seconds(120);
+        let auth = make_header_at("me", &KEY, "GET", 8000, "/", ts);
+        check(
+            rocket(),
+            Some(auth),
+            (Status::Unauthorized, "StaleTimestamp"),
+        );
+    }
+
+    #[test]
+    fn test_replayed_nonce() {
+        let client = Client::new(
+            rocket().manage(HawkConfig::default().nonce_store(MemoryNonceStore::new())),
+        )
+        .unwrap();
+        let get = |auth: Header<'static>| {
+            let mut res = client
+                .get("/")
+                .header(Header::new("Host", "localhost:8000"))
+                .header(auth)
+                .dispatch();
+            (res.status(), res.body_string().unwrap())
+        };
+
+        // a forged request with the same timestamp and nonce does not use up the nonce
+        let ts = time::now().to_timespec();
+        let forged = make_header_at("me", &[1u8; 32], "GET", 8000, "/", ts);
+        assert_eq!(get(forged), (Status::Unauthorized, "BadMac".to_string()));
+
+        let auth = make_header_at("me", &KEY, "GET", 8000, "/", ts);
+        assert_eq!(get(auth.clone()), (Status::Ok, "me".to_string()));
+        assert_eq!(
+            get(auth),
+            (Status::Unauthorized, "ReplayedNonce".to_string())
+        );
+    }
+
+    #[test]
+    fn test_replay_policy() {
+        #[post("/")]
+        fn create(hawk: HawkAuthenticated) -> String {
+            hawk.id().to_string()
+        }
+
+        let credentials = HawkCredentials::new()
+            .add_credential(
+                "me",
+                HawkCredential::from_key(Key::new(KEY.to_vec(), &SHA256)),
+            )
+            .add_credential(
+                "trusted",
+                HawkCredential::from_key(Key::new(KEY.to_vec(), &SHA256)).check_nonce(false),
+            );
+        let config = HawkConfig::default()
+            .nonce_store(MemoryNonceStore::new())
+            .skip_nonce_check_for(Method::Get);
+        let client = Client::new(
+            rocket::ignite()
+                .manage(credentials)
+                .manage(config)
+                .mount("/", routes![index, create]),
+        )
+        .unwrap();
+        let replay = |id: &str, method: &str| {
+            let auth = make_header(id, &KEY, method, 8000, "/");
+            let method = if method == "GET" {
+                Method::Get
+            } else {
+                Method::Post
+            };
+            (0..2)
+                .map(|_| {
+                    client
+                        .req(method, "/")
+                        .header(Header::new("Host", "localhost:8000"))
+                        .header(auth.clone())
+                        .dispatch()
+                        .status()
+                })
+                .last()
+                .unwrap()
+        };
+
+        assert_eq!(replay("me", "GET"), Status::Ok);
+        assert_eq!(replay("me", "POST"), Status::Unauthorized);
+        assert_eq!(replay("trusted", "POST"), Status::Ok);
+    }
+
+    #[test]
+    fn test_replay_hook() {
+        let attempts = Arc::new(Mutex::new(vec![]));
+        let recorded = attempts.clone();
+        let config = HawkConfig::default()
+            .nonce_store(MemoryNonceStore::new())
+            .on_replay(move |attempt: &ReplayAttempt| {
+                let route = attempt.route.clone().unwrap_or_default();
+                let attempt = format!(
+                    "{} {} {} {}",
+                    attempt.id, attempt.nonce, attempt.method, route
+                );
+                recorded.lock().unwrap().push(attempt);
+            });
+        let client = Client::new(rocket().manage(config)).unwrap();
+        let auth = make_header("me", &KEY, "GET", 8000, "/");
+        for _ in 0..3 {
+            client
+                .get("/")
+                .header(Header::new("Host", "localhost:8000"))
+                .header(auth.clone())
+                .dispatch();
+        }
+
+        let config = client.rocket().state::<HawkConfig>().unwrap();
+        assert_eq!(config.replay_count(), 2);
+        assert_eq!(*attempts.lock().unwrap(), vec!["me nonce GET /"; 2]);
+    }
+
+    #[test]
+    fn test_replay_key() {
+        #[get("/other")]
+        fn other(hawk: HawkAuthenticated) -> String {
+            hawk.id().to_string()
+        }
+
+        let statuses = |replay_key| {
+            let client = Client::new(
+                rocket()
+                    .manage(
+                        HawkConfig::default()
+                            .nonce_store(MemoryNonceStore::new())
+                            .replay_key(replay_key),
+                    )
+                    .mount("/", routes![other]),
+            )
+            .unwrap();
+            // two different requests with the same timestamp and nonce
+            let ts = time::now().to_timespec();
+            ["/", "/other"]
+                .iter()
+                .map(|path| {
+                    client
+                        .get(*path)
+                        .header(Header::new("Host", "localhost:8000"))
+                        .header(make_header_at("me", &KEY, "GET", 8000, path, ts))
+                        .dispatch()
+                        .status()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            statuses(ReplayKey::Nonce),
+            vec![Status::Ok, Status::Unauthorized]
+        );
+        assert_eq!(statuses(ReplayKey::Mac), vec![Status::Ok, Status::Ok]);
+    }
+
+    #[test]
+    fn test_extreme_timestamp() {
+        for &sec in &[i64::MIN, i64::MAX] {
+            let auth = make_header_at("me", &KEY, "GET", 8000, "/", Timespec::new(sec, 0));
+            check(
+                rocket(),
+                Some(auth),
+                (Status::Unauthorized, "StaleTimestamp"),
+            );
+        }
+    }
+
+    #[test]
+    fn test_future_timestamp() {
+        let ts = time::now().to_timespec() + Duration::seconds(120);
+        let auth = make_header_at("me", &KEY, "GET", 8000, "/", ts);
+        check(
+            rocket(),
+            Some(auth),
+            (Status::Unauthorized, "StaleTimestamp"),
+        );
+    }
+
+    #[test]
+    fn test_configured_skew() {
+        let ts = time::now().to_timespec() - Duration::seconds(120);
+        let auth = make_header_at("me", &KEY, "GET", 8000, "/", ts);
+        let rocket = rocket().manage(HawkConfig::default().ts_skew(Duration::seconds(300)));
+        check(rocket, Some(auth), (Status::Ok, "me"));
+    }
+
+    #[test]
+    fn test_stale_timestamp_bad_mac() {
+        // a forged header with a stale timestamp is reported as a bad MAC
+        let ts = time::now().to_timespec() - Duration::seconds(120);
+        let auth = make_header_at("me", &[1u8; 32], "GET", 8000, "/", ts);
+        check(rocket(), Some(auth), (Status::Unauthorized, "BadMac"));
+    }
+
+    #[test]
+    fn test_policies() {
+        #[get("/read")]
+        fn read(hawk: Result<HawkAuthenticated<Lenient>, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Ok(h) => status::Custom(Status::Ok, h.id().to_string()),
+                Err(e) => status::Custom(Status::Unauthorized, format!("{:?}", e)),
+            }
+        }
+
+        #[get("/write")]
+        fn write(hawk: Result<HawkAuthenticated<Strict>, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Ok(h) => status::Custom(Status::Ok, h.id().to_string()),
+                Err(e) => status::Custom(Status::Unauthorized, format!("{:?}", e)),
+            }
+        }
+
+        let credentials = HawkCredentials::new().add("me", Key::new(KEY.to_vec(), &SHA256));
+        let client = Client::new(
+            rocket::ignite()
+                .manage(credentials)
+                .mount("/", routes![read, write]),
+        )
+        .unwrap();
+        let get = |path: &str, auth: Header<'static>| {
+            let mut res = client
+                .get(path.to_string())
+                .header(Header::new("Host", "localhost:8000"))
+                .header(auth)
+                .dispatch();
+            (res.status(), res.body_string().unwrap())
+        };
+
+        // the lenient policy accepts a timestamp beyond the default skew
+        let ts = time::now().to_timespec() - Duration::seconds(120);
+        let auth = make_header_at("me", &KEY, "GET", 8000, "/read", ts);
+        assert_eq!(get("/read", auth), (Status::Ok, "me".to_string()));
+
+        // the strict policy requires a payload hash
+        let auth = make_header("me", &KEY, "GET", 8000, "/write");
+        assert_eq!(
+            get("/write", auth),
+            (Status::Unauthorized, "MissingField(Hash)".to_string())
+        );
+
+        // the strict policy requires a nonce check, so a nonce store must be configured
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new(KEY.to_vec(), &SHA256),
+        };
+        let hashed = RequestBuilder::new("GET", "localhost", 8000, "/write")
+            .hash(&[0u8; 32][..])
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+        let auth = Header::new("Authorization", format!("Hawk {}", hashed));
+        assert_eq!(
+            get("/write", auth),
+            (Status::Unauthorized, "NoNonceStore".to_string())
+        );
+    }
+
+    #[test]
+    fn test_policy_narrows_credential_skew() {
+        #[get("/")]
+        fn index(hawk: Result<HawkAuthenticated<Lenient>, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Ok(h) => status::Custom(Status::Ok, h.id().to_string()),
+                Err(e) => status::Custom(Status::Unauthorized, format!("{:?}", e)),
+            }
+        }
+
+        let rocket = |skew| {
+            let credential =
+                HawkCredential::from_key(Key::new(KEY.to_vec(), &SHA256)).ts_skew(skew);
+            rocket::ignite()
+                .manage(HawkCredentials::new().add_credential("me", credential))
+                .mount("/", routes![index])
+        };
+        let ts = time::now().to_timespec() - Duration::seconds(120);
+        let auth = make_header_at("me", &KEY, "GET", 8000, "/", ts);
+
+        // a credential may narrow the lenient policy's skew, but not widen it
+        check(
+            rocket(Duration::seconds(60)),
+            Some(auth.clone()),
+            (Status::Unauthorized, "StaleTimestamp"),
+        );
+        let ts = time::now().to_timespec() - Duration::seconds(600);
+        let stale = make_header_at("me", &KEY, "GET", 8000, "/", ts);
+        check(
+            rocket(Duration::seconds(900)),
+            Some(stale),
+            (Status::Unauthorized, "StaleTimestamp"),
+        );
+        check(
+            rocket(Duration::seconds(900)),
+            Some(auth),
+            (Status::Ok, "me"),
+        );
+    }
+
+    // make a Hawk header with the given app and dlg attributes
+    fn make_header_app(app: &str, dlg: Option<&str>) -> Header<'static> {
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new(KEY.to_vec(), &SHA256),
+        };
+        let header = RequestBuilder::new("GET", "localhost", 8000, "/")
+            .app(app)
+            .dlg(dlg)
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+        Header::new("Authorization", format!("Hawk {}", header))
+    }
+
+    fn app_rocket() -> Rocket {
+        let credentials = HawkCredentials::new()
+            .add("me", Key::new(KEY.to_vec(), &SHA256))
+            .app_validator(|id, app| id == "me" && app == Some("my-app"))
+            .dlg_validator(|_, _, dlg| dlg.is_none() || dlg == Some("friend"));
+        rocket::ignite()
+            .manage(credentials)
+            .mount("/", routes![index])
+    }
+
+    #[test]
+    fn test_app_valid() {
+        let auth = make_header_app("my-app", Some("friend"));
+        check(app_rocket(), Some(auth), (Status::Ok, "me"));
+    }
+
+    #[test]
+    fn test_app_missing() {
+        let auth = make_header("me", &KEY, "GET", 8000, "/");
+        check(app_rocket(), Some(auth), (Status::Unauthorized, "BadApp"));
+    }
+
+    #[test]
+    fn test_app_wrong() {
+        let auth = make_header_app("other-app", None);
+        check(app_rocket(), Some(auth), (Status::Unauthorized, "BadApp"));
+    }
+
+    #[test]
+    fn test_dlg_wrong() {
+        let auth = make_header_app("my-app", Some("stranger"));
+        check(app_rocket(), Some(auth), (Status::Unauthorized, "BadDlg"));
+    }
+
+    #[test]
+    fn test_no_credentials() {
+        let rocket = rocket::ignite().mount("/", routes![index]);
+        let auth = make_header("me", &KEY, "GET", 8000, "/");
+        check(rocket, Some(auth), (Status::Unauthorized, "NoCredentials"));
+    }
+}
@@ -0,0 +1,156 @@
+use super::{NonceStore, NonceStoreError};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// An approximate nonce store using a pair of rotating Bloom filters, which uses constant memory
+/// however many requests are received.  In exchange, a small fraction of new nonces are wrongly
+/// reported as replayed, failing with `HawkError::ReplayedNonce`; clients should retry such
+/// requests with a new nonce.
+///
+/// Nonces are recorded in the current filter.  Every window, the previous filter is discarded
+/// and the current filter becomes the previous one, so each nonce is remembered for between one
+/// and two windows.  The window is the longest time for which the guard has asked the store to
+/// retain a nonce.
+pub struct BloomNonceStore {
+    bits: usize,
+    hashes: u32,
+    hashers: (RandomState, RandomState),
+    state: Mutex<State>,
+}
+
+struct State {
+    current: Vec<u64>,
+    previous: Vec<u64>,
+    rotated: Instant,
+    window: Duration,
+}
+
+impl BloomNonceStore {
+    /// Create a store sized for the given number of requests per window, with at most the given
+    /// false-positive rate, such as `0.0001`.
+    pub fn new(requests: usize, false_positive_rate: f64) -> Self {
+        // both filters are checked, so each gets half of the false-positive budget
+        let (bits, hashes) = filter_size(requests.max(1), false_positive_rate / 2.0);
+        let words = (bits + 63) / 64;
+        BloomNonceStore {
+            bits: words * 64,
+            hashes,
+            hashers: (RandomState::new(), RandomState::new()),
+            state: Mutex::new(State {
+                current: vec![0; words],
+                previous: vec![0; words],
+                rotated: Instant::now(),
+                window: Duration::from_secs(0),
+            }),
+        }
+    }
+
+    // The bit positions for a nonce, using double hashing.
+    fn positions(&self, key: &str) -> Vec<usize> {
+        let h1 = self.hashers.0.hash_one(key);
+        let h2 = self.hashers.1.hash_one(key) | 1;
+        (0..u64::from(self.hashes))
+            .map(|i| (h1.wrapping_add(i.wrapping_mul(h2)) % self.bits as u64) as usize)
+            .collect()
+    }
+}
+
+impl NonceStore for BloomNonceStore {
+    fn check_and_store(&self, key: &str, retain: Duration) -> Result<bool, NonceStoreError> {
+        let positions = self.positions(key);
+        let mut state = self.state.lock().unwrap();
+        state.window = state.window.max(retain);
+        let elapsed = state.rotated.elapsed();
+        if elapsed >= state.window * 2 {
+            state.previous.iter_mut().for_each(|w| *w = 0);
+            state.current.iter_mut().for_each(|w| *w = 0);
+            state.rotated = Instant::now();
+        } else if elapsed >= state.window {
+            let State {
+                current,
+                previous,
+                rotated,
+                window,
+            } = &mut *state;
+            std::mem::swap(current, previous);
+            current.iter_mut().for_each(|w| *w = 0);
+            *rotated += *window;
+        }
+
+        let contains = |filter: &[u64]| {
+            positions
+                .iter()
+                .all(|&p| filter[p / 64] & (1 << (p % 64)) != 0)
+        };
+        if contains(&state.current) || contains(&state.previous) {
+            return Ok(false);
+        }
+        for &p in &positions {
+            state.current[p / 64] |= 1 << (p % 64);
+        }
+        Ok(true)
+    }
+}
+
+impl fmt::Debug for BloomNonceStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BloomNonceStore")
+            .field("bits", &self.bits)
+            .field("hashes", &self.hashes)
+            .finish()
+    }
+}
+
+// Calculate the optimal number of bits and hash functions for a Bloom filter holding the given
+// number of items with the given false-positive rate.
+fn filter_size(items: usize, false_positive_rate: f64) -> (usize, u32) {
+    let rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+    let ln2 = std::f64::consts::LN_2;
+    let bits = (-(items as f64) * rate.ln() / (ln2 * ln2)).ceil().max(64.0);
+    let hashes = (bits / items as f64 * ln2).round().max(1.0);
+    (bits as usize, hashes as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{filter_size, BloomNonceStore};
+    use crate::NonceStore;
+    use std::time::Duration;
+
+    const RETAIN: Duration = Duration::from_secs(120);
+
+    #[test]
+    fn test_filter_size() {
+        // about 9.6 bits and 7 hashes per item for a 1% false-positive rate
+        let (bits, hashes) = filter_size(1000, 0.01);
+        assert_eq!((bits, hashes), (9586, 7));
+    }
+
+    #[test]
+    fn test_replay() {
+        let store = BloomNonceStore::new(10_000, 0.001);
+        assert_eq!(store.check_and_store("a", RETAIN), Ok(true));
+        assert_eq!(store.check_and_store("a", RETAIN), Ok(false));
+        assert_eq!(store.check_and_store("b", RETAIN), Ok(true));
+    }
+
+    #[test]
+    fn test_false_positives() {
+        let store = BloomNonceStore::new(10_000, 0.01);
+        let rejected = (0..10_000)
+            .filter(|n| store.check_and_store(&n.to_string(), RETAIN) == Ok(false))
+            .count();
+        // well within the budget, allowing for chance
+        assert!(rejected < 100, "{} false positives", rejected);
+    }
+
+    #[test]
+    fn test_rotation() {
+        let store = BloomNonceStore::new(100, 0.01);
+        assert_eq!(store.check_and_store("a", Duration::from_secs(0)), Ok(true));
+        assert_eq!(store.check_and_store("a", Duration::from_secs(0)), Ok(true));
+    }
+}
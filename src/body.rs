@@ -0,0 +1,815 @@
+use super::config::get_config;
+use super::{
+    AuthorizationHeader, HawkAlgorithm, HawkAuthenticated, HawkError, HawkPrincipal,
+    NonceGenerator, PayloadMismatch, RandomNonce,
+};
+use hawk::PayloadHasher;
+use ring::constant_time::verify_slices_are_equal;
+use ring::digest;
+use rocket::data::{self, Data, FromDataSimple};
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::Outcome;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+// The body size limit when no "hawk" limit is configured.
+const DEFAULT_LIMIT: u64 = 1024 * 1024;
+
+/// A type that can be produced from a request body verified by `HawkVerifiedBody`.  The body
+/// is written to a buffer as it is read and hashed, then converted once the hash matches.
+pub trait FromHawkBody: Sized {
+    /// The destination of the body as it is read.
+    type Buffer: Write;
+
+    /// Create an empty buffer for the body of the given request.
+    fn buffer(request: &Request) -> io::Result<Self::Buffer>;
+
+    /// Convert the verified body, or fail with a reason, resulting in `HawkError::BadBody`.
+    fn from_body(request: &Request, body: Self::Buffer) -> Result<Self, String>;
+}
+
+impl FromHawkBody for Vec<u8> {
+    type Buffer = Vec<u8>;
+
+    fn buffer(_request: &Request) -> io::Result<Self::Buffer> {
+        Ok(vec![])
+    }
+
+    fn from_body(_request: &Request, body: Vec<u8>) -> Result<Self, String> {
+        Ok(body)
+    }
+}
+
+impl FromHawkBody for String {
+    type Buffer = Vec<u8>;
+
+    fn buffer(_request: &Request) -> io::Result<Self::Buffer> {
+        Ok(vec![])
+    }
+
+    fn from_body(_request: &Request, body: Vec<u8>) -> Result<Self, String> {
+        String::from_utf8(body).map_err(|e| e.to_string())
+    }
+}
+
+/// A request body streamed to a temporary file, for uploads too large to hold in memory.  The
+/// file is removed when this is dropped, unless it is moved elsewhere with `persist`.
+#[derive(Debug)]
+pub struct HawkUpload {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl HawkUpload {
+    /// The path of the temporary file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Open the temporary file for reading.
+    pub fn open(&self) -> io::Result<File> {
+        File::open(&self.path)
+    }
+
+    /// Move the file to the given path, on the same filesystem as the temporary directory.
+    pub fn persist<P: AsRef<Path>>(mut self, path: P) -> io::Result<()> {
+        self.file = None;
+        fs::rename(&self.path, path)?;
+        self.path = PathBuf::new();
+        Ok(())
+    }
+}
+
+impl FromHawkBody for HawkUpload {
+    type Buffer = HawkUpload;
+
+    fn buffer(_request: &Request) -> io::Result<Self::Buffer> {
+        let name = format!("rocket-hawk-{}", RandomNonce::new().generate());
+        let path = std::env::temp_dir().join(name);
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(HawkUpload {
+            path,
+            file: Some(file),
+        })
+    }
+
+    fn from_body(_request: &Request, mut body: HawkUpload) -> Result<Self, String> {
+        body.flush().map_err(|e| e.to_string())?;
+        body.file = None;
+        Ok(body)
+    }
+}
+
+impl Write for HawkUpload {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.file {
+            Some(ref mut file) => file.write(buf),
+            None => Err(io::Error::new(io::ErrorKind::Other, "upload is complete")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file {
+            Some(ref mut file) => file.sync_all(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for HawkUpload {
+    fn drop(&mut self) {
+        if self.path != PathBuf::new() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// A data guard to require that the request body match the payload hash (`hash`) in the
+/// request's Hawk header, the half of Hawk's integrity protection that request guards cannot
+/// provide.  The body is hashed along with the request's Content-Type as it streams in, and
+/// only converted to `T` and handed to the route once the hash matches.  Use `HawkUpload` for
+/// `T` to avoid holding large bodies in memory, or `HawkMultipart` for form uploads.
+///
+/// This does not validate the header itself, so use it with a request guard that does, such
+/// as `HawkAuthenticated`; the header's MAC covers the hash.
+///
+/// Requests with a body but no hash in the header fail with `HawkError::MissingPayloadHash`,
+/// and those whose body does not match the hash with `HawkError::BadPayloadHash`, though both
+/// can be relaxed where the body is empty or unhashed; see `HawkConfig::body_without_hash`
+/// and `HawkConfig::hash_without_body`.  Bodies longer than the
+/// "hawk" limit in the Rocket configuration, defaulting to 1MiB, fail with
+/// `HawkError::BodyTooLarge`: immediately if the Content-Length exceeds the limit, and
+/// otherwise as soon as the limit is passed, before the excess is hashed.
+///
+/// Rocket offers no way to hand an already-read body to another data guard, so `T` is
+/// produced with `FromHawkBody` rather than `FromData`.
+#[derive(Debug)]
+pub struct HawkVerifiedBody<T>(T);
+
+impl<T> HawkVerifiedBody<T> {
+    /// Get the body, consuming the guard.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: FromHawkBody> FromDataSimple for HawkVerifiedBody<T> {
+    type Error = HawkError;
+
+    fn from_data(request: &Request, data: Data) -> data::Outcome<Self, Self::Error> {
+        let header = match request.guard::<AuthorizationHeader>() {
+            Outcome::Success(header) => header,
+            Outcome::Failure(failure) => return Outcome::Failure(failure),
+            Outcome::Forward(()) => return Outcome::Forward(data),
+        };
+        // reject bodies declared too large before doing any work on them
+        let limit = request.limits().get("hawk").unwrap_or(DEFAULT_LIMIT);
+        let declared = request
+            .headers()
+            .get_one("Content-Length")
+            .and_then(|l| l.trim().parse::<u64>().ok());
+        if declared.map(|l| l > limit).unwrap_or(false) {
+            return Outcome::Failure((Status::PayloadTooLarge, HawkError::BodyTooLarge));
+        }
+
+        let config = get_config(request);
+        let expected = header.hash.as_ref();
+        // the hash is made with the credential's digest, identified here by its length
+        let algorithm = match expected.map(|hash| HawkAlgorithm::from_mac_len(hash.len())) {
+            Some(Some(algorithm)) => Some(algorithm),
+            Some(None) => {
+                return Outcome::Failure((Status::Unauthorized, HawkError::BadPayloadHash))
+            }
+            None => None,
+        };
+
+        let content_type = request.headers().get_one("Content-Type").unwrap_or("");
+        let mut verifier = expected
+            .zip(algorithm)
+            .map(|(hash, a)| PayloadVerifier::new(content_type, a, hash.clone()));
+        let mut body = match T::buffer(request) {
+            Ok(body) => body,
+            Err(e) => {
+                let e = HawkError::BadBody(e.to_string());
+                return Outcome::Failure((Status::InternalServerError, e));
+            }
+        };
+
+        let mut stream = data.open().take(limit + 1);
+        let mut chunk = [0u8; 8192];
+        let mut len = 0;
+        loop {
+            let n = match stream.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    let e = HawkError::BadBody(e.to_string());
+                    return Outcome::Failure((Status::BadRequest, e));
+                }
+            };
+            len += n as u64;
+            if len > limit {
+                return Outcome::Failure((Status::PayloadTooLarge, HawkError::BodyTooLarge));
+            }
+            if let Some(ref mut verifier) = verifier {
+                verifier.update(&chunk[..n]);
+            }
+            if let Err(e) = body.write_all(&chunk[..n]) {
+                let e = HawkError::BadBody(e.to_string());
+                return Outcome::Failure((Status::InternalServerError, e));
+            }
+        }
+
+        match verifier {
+            Some(verifier) => {
+                if verifier.verify().is_err() {
+                    let policy = if len == 0 {
+                        config.hash_without_body
+                    } else {
+                        PayloadMismatch::Reject
+                    };
+                    let message = "Hawk payload hash does not match the empty request body";
+                    if !policy.allows(message) {
+                        return Outcome::Failure((Status::Unauthorized, HawkError::BadPayloadHash));
+                    }
+                }
+            }
+            None => {
+                let message = "Request body is not covered by a Hawk payload hash";
+                if len > 0 && !config.body_without_hash.allows(message) {
+                    return Outcome::Failure((Status::BadRequest, HawkError::MissingPayloadHash));
+                }
+            }
+        }
+
+        match T::from_body(request, body) {
+            Ok(body) => Outcome::Success(HawkVerifiedBody(body)),
+            Err(e) => Outcome::Failure((Status::BadRequest, HawkError::BadBody(e))),
+        }
+    }
+}
+
+impl<T> Deref for HawkVerifiedBody<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// A data guard that authenticates the request, as for `HawkAuthenticated`, then streams the
+/// body to a temporary file, verifying it against the header's payload hash as for
+/// `HawkVerifiedBody<HawkUpload>`.  It provides both the authenticated principal and the
+/// verified file, so that large verified uploads need not be held in memory.
+///
+/// The body is not read at all unless the request is authenticated.
+#[derive(Debug)]
+pub struct HawkVerifiedUpload {
+    principal: HawkPrincipal,
+    upload: HawkUpload,
+}
+
+impl HawkVerifiedUpload {
+    /// The authenticated principal.
+    pub fn principal(&self) -> &HawkPrincipal {
+        &self.principal
+    }
+
+    /// The verified upload.
+    pub fn upload(&self) -> &HawkUpload {
+        &self.upload
+    }
+
+    /// Get the principal and the upload, consuming the guard.
+    pub fn into_parts(self) -> (HawkPrincipal, HawkUpload) {
+        (self.principal, self.upload)
+    }
+}
+
+impl FromDataSimple for HawkVerifiedUpload {
+    type Error = HawkError;
+
+    fn from_data(request: &Request, data: Data) -> data::Outcome<Self, Self::Error> {
+        let principal = match request.guard::<HawkAuthenticated>() {
+            Outcome::Success(hawk) => hawk.into_inner(),
+            Outcome::Failure(failure) => return Outcome::Failure(failure),
+            Outcome::Forward(()) => return Outcome::Forward(data),
+        };
+        let upload = HawkVerifiedBody::<HawkUpload>::from_data(request, data)?.into_inner();
+        Outcome::Success(HawkVerifiedUpload { principal, upload })
+    }
+}
+
+/// An in-progress check of a request body against a payload hash, for routes that read the
+/// request's `Data` themselves, such as to proxy or stream it elsewhere.  Get one from
+/// `HawkPrincipal::payload_verifier`, feed it each chunk of the body with `update`, and call
+/// `verify` once the body has been read.
+pub struct PayloadVerifier {
+    hasher: PayloadHasher,
+    expected: Vec<u8>,
+}
+
+impl PayloadVerifier {
+    /// Create a verifier for a body with the given Content-Type header value, expected to
+    /// have the given payload hash made with the given algorithm.
+    pub fn new(content_type: &str, algorithm: HawkAlgorithm, expected: Vec<u8>) -> Self {
+        let content_type = normalize_content_type(content_type);
+        PayloadVerifier {
+            hasher: PayloadHasher::new(content_type, algorithm.digest()),
+            expected,
+        }
+    }
+
+    /// Add the next chunk of the body.
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    /// Check the hash of the body, failing with `HawkError::BadPayloadHash` if it does not
+    /// match.
+    pub fn verify(self) -> Result<(), HawkError> {
+        verify_slices_are_equal(&self.hasher.finish(), &self.expected)
+            .map_err(|_| HawkError::BadPayloadHash)
+    }
+
+    /// Wrap a reader of the body, such as the `DataStream` from `Data::open`, so that the body
+    /// is verified as it is read.  See `VerifyingReader`.
+    pub fn reader<R: Read>(self, inner: R) -> VerifyingReader<R> {
+        VerifyingReader {
+            inner,
+            verifier: Some(self),
+            verified: None,
+        }
+    }
+}
+
+/// A reader that passes a request body through unchanged while hashing it, for routes that
+/// forward the raw `Data` elsewhere.  Once the body ends, the hash is checked: if it does not
+/// match, that read, and every later one, fails with an `InvalidData` error instead of
+/// reporting the end of the body, so a proxy copying the body aborts rather than completing
+/// the upload.  Create one with `PayloadVerifier::reader`.
+#[derive(Debug)]
+pub struct VerifyingReader<R> {
+    inner: R,
+    verifier: Option<PayloadVerifier>,
+    verified: Option<bool>,
+}
+
+impl<R> VerifyingReader<R> {
+    /// Whether the body matched its hash, or `None` if the body has not yet been read to the
+    /// end.
+    pub fn verified(&self) -> Option<bool> {
+        self.verified
+    }
+
+    /// Get the wrapped reader, consuming this one.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for VerifyingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.verified == Some(false) {
+            return Err(mismatch());
+        }
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            if let Some(ref mut verifier) = self.verifier {
+                verifier.update(&buf[..n]);
+            }
+        } else if let Some(verifier) = self.verifier.take() {
+            let verified = verifier.verify().is_ok();
+            self.verified = Some(verified);
+            if !verified {
+                return Err(mismatch());
+            }
+        }
+        Ok(n)
+    }
+}
+
+// The error reported by a VerifyingReader whose body does not match its hash.
+fn mismatch() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "request body does not match the Hawk payload hash",
+    )
+}
+
+impl fmt::Debug for PayloadVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PayloadVerifier")
+            .field("expected", &self.expected)
+            .finish()
+    }
+}
+
+/// Normalize a Content-Type header value for payload hashing, as the Hawk specification
+/// requires: parameters such as `charset` are removed, along with surrounding whitespace, and
+/// the remainder is lower-cased.  A missing Content-Type is the empty string.
+pub fn normalize_content_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_lowercase()
+}
+
+/// Build the normalized payload string that is hashed for a Hawk payload hash:
+/// `hawk.1.payload\n<content-type>\n<body>\n`, with the content type normalized by
+/// `normalize_content_type`.
+pub fn normalize_payload(content_type: &str, body: &[u8]) -> Vec<u8> {
+    let content_type = normalize_content_type(content_type);
+    let mut normalized = Vec::with_capacity(body.len() + content_type.len() + 17);
+    normalized.extend_from_slice(b"hawk.1.payload\n");
+    normalized.extend_from_slice(content_type.as_bytes());
+    normalized.push(b'\n');
+    normalized.extend_from_slice(body);
+    normalized.push(b'\n');
+    normalized
+}
+
+/// Calculate the Hawk payload hash of a body with the given Content-Type header value, as
+/// expected in the `hash` attribute and checked by `HawkVerifiedBody`.
+pub fn payload_hash(content_type: &str, body: &[u8], algorithm: HawkAlgorithm) -> Vec<u8> {
+    let normalized = normalize_payload(content_type, body);
+    digest::digest(algorithm.digest(), &normalized)
+        .as_ref()
+        .to_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{normalize_content_type, normalize_payload, payload_hash};
+    use super::{HawkUpload, HawkVerifiedBody, HawkVerifiedUpload};
+    use crate::PayloadMismatch;
+    use crate::{HawkAlgorithm, HawkAuthenticated, HawkConfig, HawkCredentials, HawkError};
+    use hawk::{Credentials, Key, PayloadHasher, RequestBuilder, SHA256};
+    use rocket::config::{Config, Environment, Limits};
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::Client;
+    use rocket::response::status;
+    use rocket::Data;
+    use std::io::Read;
+
+    const KEY: [u8; 32] = [99u8; 32];
+
+    #[post("/", data = "<body>")]
+    fn create(
+        _hawk: HawkAuthenticated,
+        body: Result<HawkVerifiedBody<String>, HawkError>,
+    ) -> status::Custom<String> {
+        match body {
+            Ok(body) => status::Custom(Status::Ok, body.into_inner()),
+            Err(e) => status::Custom(Status::BadRequest, format!("{:?}", e)),
+        }
+    }
+
+    #[post("/upload", data = "<body>")]
+    fn upload(
+        _hawk: HawkAuthenticated,
+        body: Result<HawkVerifiedBody<HawkUpload>, HawkError>,
+    ) -> status::Custom<String> {
+        match body {
+            Ok(body) => {
+                let upload = body.into_inner();
+                let mut contents = String::new();
+                upload
+                    .open()
+                    .unwrap()
+                    .read_to_string(&mut contents)
+                    .unwrap();
+                status::Custom(Status::Ok, contents)
+            }
+            Err(e) => status::Custom(Status::BadRequest, format!("{:?}", e)),
+        }
+    }
+
+    #[post("/raw", data = "<data>")]
+    fn raw(hawk: HawkAuthenticated, content_type: &ContentType, data: Data) -> String {
+        let mut verifier = hawk.payload_verifier(&content_type.to_string()).unwrap();
+        let mut body = vec![];
+        data.stream_to(&mut body).unwrap();
+        verifier.update(&body);
+        match verifier.verify() {
+            Ok(()) => String::from_utf8(body).unwrap(),
+            Err(e) => format!("{:?}", e),
+        }
+    }
+
+    #[post("/proxy", data = "<data>")]
+    fn proxy(hawk: HawkAuthenticated, content_type: &ContentType, data: Data) -> String {
+        let verifier = hawk.payload_verifier(&content_type.to_string()).unwrap();
+        let mut reader = verifier.reader(data.open());
+        let mut body = vec![];
+        match std::io::copy(&mut reader, &mut body) {
+            Ok(_) => format!(
+                "{} {:?}",
+                String::from_utf8(body).unwrap(),
+                reader.verified()
+            ),
+            Err(e) => format!("{} {:?}", e, reader.verified()),
+        }
+    }
+
+    #[post("/file", data = "<upload>")]
+    fn file(upload: Result<HawkVerifiedUpload, HawkError>) -> String {
+        match upload {
+            Ok(upload) => {
+                let (principal, upload) = upload.into_parts();
+                let mut contents = String::new();
+                upload
+                    .open()
+                    .unwrap()
+                    .read_to_string(&mut contents)
+                    .unwrap();
+                format!("{} {}", principal.id(), contents)
+            }
+            Err(e) => format!("{:?}", e),
+        }
+    }
+
+    // post the given body, with a header carrying the hash of the given payload, if any
+    fn post(content_type: ContentType, body: &str, hashed: Option<&str>) -> String {
+        post_to(HawkConfig::default(), "/", content_type, body, hashed)
+    }
+
+    fn post_to(
+        hawk_config: HawkConfig,
+        path: &str,
+        content_type: ContentType,
+        body: &str,
+        hashed: Option<&str>,
+    ) -> String {
+        let config = Config::build(Environment::Development)
+            .limits(Limits::new().limit("hawk", 16))
+            .unwrap();
+        let credentials = HawkCredentials::new().add("me", Key::new(KEY.to_vec(), &SHA256));
+        let client = Client::new(
+            rocket::custom(config)
+                .manage(credentials)
+                .manage(hawk_config)
+                .mount("/", routes![create, upload, raw, proxy, file]),
+        )
+        .unwrap();
+
+        let hash = hashed.map(|p| PayloadHasher::hash("text/plain", &SHA256, p));
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new(KEY.to_vec(), &SHA256),
+        };
+        let header = RequestBuilder::new("POST", "localhost", 8000, path)
+            .hash(hash.as_deref())
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+        let mut res = client
+            .post(path)
+            .header(Header::new("Host", "localhost:8000"))
+            .header(Header::new("Authorization", format!("Hawk {}", header)))
+            .header(content_type)
+            .body(body)
+            .dispatch();
+        res.body_string().unwrap()
+    }
+
+    #[test]
+    fn test_valid() {
+        assert_eq!(post(ContentType::Plain, "hello", Some("hello")), "hello");
+    }
+
+    #[test]
+    fn test_mismatch() {
+        assert_eq!(
+            post(ContentType::Plain, "goodbye", Some("hello")),
+            "BadPayloadHash"
+        );
+    }
+
+    #[test]
+    fn test_content_type() {
+        // the hash covers the content type as well as the body
+        assert_eq!(
+            post(ContentType::JSON, "hello", Some("hello")),
+            "BadPayloadHash"
+        );
+    }
+
+    #[test]
+    fn test_missing() {
+        assert_eq!(
+            post(ContentType::Plain, "hello", None),
+            "MissingPayloadHash"
+        );
+    }
+
+    #[test]
+    fn test_body_without_hash() {
+        // an empty body needs no hash
+        assert_eq!(post(ContentType::Plain, "", None), "");
+
+        let post = |action| {
+            let config = HawkConfig::default().body_without_hash(action);
+            post_to(config, "/", ContentType::Plain, "hello", None)
+        };
+        assert_eq!(post(PayloadMismatch::Reject), "MissingPayloadHash");
+        assert_eq!(post(PayloadMismatch::Warn), "hello");
+        assert_eq!(post(PayloadMismatch::Ignore), "hello");
+    }
+
+    #[test]
+    fn test_hash_without_body() {
+        // a hash of the empty body matches
+        assert_eq!(post(ContentType::Plain, "", Some("")), "");
+
+        let post = |action, body| {
+            let config = HawkConfig::default().hash_without_body(action);
+            post_to(config, "/", ContentType::Plain, body, Some("hello"))
+        };
+        assert_eq!(post(PayloadMismatch::Reject, ""), "BadPayloadHash");
+        assert_eq!(post(PayloadMismatch::Warn, ""), "");
+        assert_eq!(post(PayloadMismatch::Ignore, ""), "");
+        // a non-empty body must always match
+        assert_eq!(post(PayloadMismatch::Ignore, "bye"), "BadPayloadHash");
+    }
+
+    #[test]
+    fn test_payload_verifier() {
+        let post = |body| {
+            post_to(
+                HawkConfig::default(),
+                "/raw",
+                ContentType::Plain,
+                body,
+                Some("hello"),
+            )
+        };
+        assert_eq!(post("hello"), "hello");
+        assert_eq!(post("howdy"), "BadPayloadHash");
+    }
+
+    #[test]
+    fn test_verifying_reader() {
+        let post = |body| {
+            post_to(
+                HawkConfig::default(),
+                "/proxy",
+                ContentType::Plain,
+                body,
+                Some("hello"),
+            )
+        };
+        assert_eq!(post("hello"), "hello Some(true)");
+        assert_eq!(
+            post("howdy"),
+            "request body does not match the Hawk payload hash Some(false)"
+        );
+    }
+
+    #[test]
+    fn test_verified_upload() {
+        let post = |body| {
+            post_to(
+                HawkConfig::default(),
+                "/file",
+                ContentType::Plain,
+                body,
+                Some("hello"),
+            )
+        };
+        assert_eq!(post("hello"), "me hello");
+        assert_eq!(post("howdy"), "BadPayloadHash");
+    }
+
+    #[test]
+    fn test_too_large() {
+        let body = "x".repeat(17);
+        assert_eq!(post(ContentType::Plain, &body, Some(&body)), "BodyTooLarge");
+        let body = "x".repeat(16);
+        assert_eq!(post(ContentType::Plain, &body, Some(&body)), body);
+    }
+
+    #[test]
+    fn test_declared_too_large() {
+        #[post("/", data = "<body>")]
+        fn unauthenticated(body: Result<HawkVerifiedBody<String>, HawkError>) -> String {
+            format!("{:?}", body.map(HawkVerifiedBody::into_inner))
+        }
+
+        let config = Config::build(Environment::Development)
+            .limits(Limits::new().limit("hawk", 16))
+            .unwrap();
+        let credentials = HawkCredentials::new().add("me", Key::new(KEY.to_vec(), &SHA256));
+        let client = Client::new(
+            rocket::custom(config)
+                .manage(credentials)
+                .mount("/", routes![unauthenticated]),
+        )
+        .unwrap();
+        let mut res = client
+            .post("/")
+            .header(Header::new(
+                "Authorization",
+                "Hawk id=\"me\", ts=\"1\", nonce=\"n\", hash=\"eA==\", mac=\"eA==\"",
+            ))
+            .header(Header::new("Content-Length", "17"))
+            .header(ContentType::Plain)
+            .body("x")
+            .dispatch();
+        // rejected without reading the body or validating the header
+        assert_eq!(res.body_string().unwrap(), "Err(BodyTooLarge)");
+    }
+
+    #[test]
+    fn test_upload() {
+        assert_eq!(
+            post_to(
+                HawkConfig::default(),
+                "/upload",
+                ContentType::Plain,
+                "hello",
+                Some("hello")
+            ),
+            "hello"
+        );
+    }
+
+    #[test]
+    fn test_normalize_content_type() {
+        assert_eq!(
+            normalize_content_type(" Application/JSON; charset=utf-8"),
+            "application/json"
+        );
+        assert_eq!(normalize_content_type("text/plain"), "text/plain");
+        assert_eq!(normalize_content_type(""), "");
+    }
+
+    #[test]
+    fn test_normalize_payload() {
+        assert_eq!(
+            normalize_payload("text/plain; charset=utf-8", b"hi"),
+            b"hawk.1.payload\ntext/plain\nhi\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_payload_hash() {
+        // the example from the Hawk specification
+        let hash = payload_hash(
+            "text/plain",
+            b"Thank you for flying Hawk",
+            HawkAlgorithm::Sha256,
+        );
+        assert_eq!(
+            base64::encode(&hash),
+            "Yi9LfIIFRtBEPt74PVmbTF/xVAwPn7ub15ePICfgnuY="
+        );
+        assert_eq!(
+            hash,
+            PayloadHasher::hash("text/plain", &SHA256, "Thank you for flying Hawk")
+        );
+    }
+
+    #[test]
+    fn test_charset() {
+        // hawk.js hashes `application/json; charset=utf-8` as `application/json`
+        let credentials = HawkCredentials::new().add("me", Key::new(KEY.to_vec(), &SHA256));
+        let client = Client::new(
+            rocket::ignite()
+                .manage(credentials)
+                .mount("/", routes![create]),
+        )
+        .unwrap();
+        let hash = payload_hash("application/json", b"{}", HawkAlgorithm::Sha256);
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new(KEY.to_vec(), &SHA256),
+        };
+        let header = RequestBuilder::new("POST", "localhost", 8000, "/")
+            .hash(&hash[..])
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+        let mut res = client
+            .post("/")
+            .header(Header::new("Host", "localhost:8000"))
+            .header(Header::new("Authorization", format!("Hawk {}", header)))
+            .header(Header::new(
+                "Content-Type",
+                "application/json; charset=UTF-8",
+            ))
+            .body("{}")
+            .dispatch();
+        assert_eq!(res.body_string().unwrap(), "{}");
+    }
+}
@@ -0,0 +1,270 @@
+use super::instrument::EventHook;
+use super::{CredentialsProvider, HawkCredential, ProviderError, ProviderEvent};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A credentials provider that caches the results of another provider, so that repeated
+/// requests from the same id do not each require a lookup in a database or other slow store.
+///
+/// Cached credentials are used for the configured time-to-live, after which they are looked up
+/// again.  Unknown ids are cached only if `negative_ttl` is set.  When the cache reaches its
+/// capacity, the least recently used entry is evicted.
+pub struct CachedProvider<P: CredentialsProvider> {
+    provider: P,
+    ttl: Duration,
+    negative_ttl: Option<Duration>,
+    capacity: usize,
+    cache: Mutex<Cache>,
+    hook: Option<Arc<EventHook>>,
+}
+
+// The cached entries, with an index of their last use, oldest first.
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<String, Entry>,
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+struct Entry {
+    fetched: Instant,
+    used: u64,
+    credentials: Vec<HawkCredential>,
+}
+
+impl<P: CredentialsProvider> CachedProvider<P> {
+    /// Wrap the given provider, caching its credentials for the given time-to-live.
+    pub fn new(provider: P, ttl: Duration) -> Self {
+        CachedProvider {
+            provider,
+            ttl,
+            negative_ttl: None,
+            capacity: 10_000,
+            cache: Mutex::new(Cache::default()),
+            hook: None,
+        }
+    }
+
+    /// Cache unknown ids for the given time-to-live, so that repeated requests with an unknown
+    /// id do not each require a lookup.  Keep this short, as newly-added credentials are not
+    /// seen until it expires.
+    ///
+    /// The default is not to cache unknown ids.
+    pub fn negative_ttl(mut self, ttl: Duration) -> Self {
+        self.negative_ttl = Some(ttl);
+        self
+    }
+
+    /// Set the maximum number of entries in the cache, including unknown ids.
+    ///
+    /// The default is 10,000.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Report `CacheHit` and `CacheMiss` events to the given hook, such as to measure the
+    /// cache's hit rate.  The hook is called synchronously, so it should be fast.
+    pub fn on_event<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ProviderEvent) + Send + Sync + 'static,
+    {
+        self.hook = Some(Arc::new(hook));
+        self
+    }
+
+    fn report(&self, event: ProviderEvent) {
+        if let Some(ref hook) = self.hook {
+            hook(&event);
+        }
+    }
+
+    /// Remove the cached entry for the given id, if any, so that the next lookup goes to the
+    /// wrapped provider.
+    pub fn invalidate(&self, id: &str) {
+        self.cache.lock().unwrap().remove(id);
+    }
+
+    /// Remove all cached entries.
+    pub fn clear(&self) {
+        *self.cache.lock().unwrap() = Cache::default();
+    }
+}
+
+impl<P: CredentialsProvider> CredentialsProvider for CachedProvider<P> {
+    fn lookup(&self, id: &str) -> Result<Option<HawkCredential>, ProviderError> {
+        Ok(self.lookup_all(id)?.into_iter().next())
+    }
+
+    fn lookup_all(&self, id: &str) -> Result<Vec<HawkCredential>, ProviderError> {
+        {
+            let mut cache = self.cache.lock().unwrap();
+            if let Some(entry) = cache.entries.get(id) {
+                let ttl = if entry.credentials.is_empty() {
+                    self.negative_ttl
+                } else {
+                    Some(self.ttl)
+                };
+                if ttl
+                    .map(|ttl| entry.fetched.elapsed() < ttl)
+                    .unwrap_or(false)
+                {
+                    let credentials = entry.credentials.clone();
+                    cache.touch(id);
+                    drop(cache);
+                    self.report(ProviderEvent::CacheHit { id });
+                    return Ok(credentials);
+                }
+            }
+        }
+        self.report(ProviderEvent::CacheMiss { id });
+
+        // the lock is not held during the lookup, so that a slow lookup does not block others;
+        // failures are not cached, so that the next lookup tries the provider again
+        let credentials = self.provider.lookup_all(id)?;
+        if !credentials.is_empty() || self.negative_ttl.is_some() {
+            let mut cache = self.cache.lock().unwrap();
+            cache.insert(id, credentials.clone());
+            while cache.entries.len() > self.capacity {
+                cache.evict_oldest();
+            }
+        }
+        Ok(credentials)
+    }
+}
+
+impl Cache {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    // Mark the entry for the id as most recently used.
+    fn touch(&mut self, id: &str) {
+        let tick = self.next_tick();
+        if let Some(entry) = self.entries.get_mut(id) {
+            let id = self.recency.remove(&entry.used).unwrap_or_default();
+            entry.used = tick;
+            self.recency.insert(tick, id);
+        }
+    }
+
+    fn insert(&mut self, id: &str, credentials: Vec<HawkCredential>) {
+        self.remove(id);
+        let used = self.next_tick();
+        self.recency.insert(used, id.to_string());
+        let entry = Entry {
+            fetched: Instant::now(),
+            used,
+            credentials,
+        };
+        self.entries.insert(id.to_string(), entry);
+    }
+
+    fn remove(&mut self, id: &str) {
+        if let Some(entry) = self.entries.remove(id) {
+            self.recency.remove(&entry.used);
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        let oldest = self.recency.keys().next().cloned();
+        if let Some(id) = oldest.and_then(|tick| self.recency.remove(&tick)) {
+            self.entries.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CachedProvider;
+    use crate::{CredentialsProvider, HawkAlgorithm, HawkCredential, ProviderError};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    // a provider that counts its lookups, knowing only the id "me", and failing for the id
+    // "down"
+    #[derive(Default)]
+    struct CountingProvider(AtomicUsize);
+
+    impl CredentialsProvider for CountingProvider {
+        fn lookup(&self, id: &str) -> Result<Option<HawkCredential>, ProviderError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            match id {
+                "me" => Ok(Some(HawkCredential::new(
+                    vec![1u8; 32],
+                    HawkAlgorithm::Sha256,
+                ))),
+                "down" => Err(ProviderError("down".to_string())),
+                _ => Ok(None),
+            }
+        }
+    }
+
+    #[test]
+    fn test_cached() {
+        let cached = CachedProvider::new(CountingProvider::default(), Duration::from_secs(60));
+        assert!(cached.lookup("me").unwrap().is_some());
+        assert!(cached.lookup("me").unwrap().is_some());
+        assert_eq!(cached.provider.0.load(Ordering::SeqCst), 1);
+
+        cached.invalidate("me");
+        assert!(cached.lookup("me").unwrap().is_some());
+        assert_eq!(cached.provider.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_expired() {
+        let cached = CachedProvider::new(CountingProvider::default(), Duration::from_secs(0));
+        assert!(cached.lookup("me").unwrap().is_some());
+        assert!(cached.lookup("me").unwrap().is_some());
+        assert_eq!(cached.provider.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_unknown_not_cached() {
+        let cached = CachedProvider::new(CountingProvider::default(), Duration::from_secs(60));
+        assert!(cached.lookup("you").unwrap().is_none());
+        assert!(cached.lookup("you").unwrap().is_none());
+        assert_eq!(cached.provider.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_negative_ttl() {
+        let cached = CachedProvider::new(CountingProvider::default(), Duration::from_secs(60))
+            .negative_ttl(Duration::from_secs(60));
+        assert!(cached.lookup("you").unwrap().is_none());
+        assert!(cached.lookup("you").unwrap().is_none());
+        assert_eq!(cached.provider.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_failure_not_cached() {
+        let cached = CachedProvider::new(CountingProvider::default(), Duration::from_secs(60))
+            .negative_ttl(Duration::from_secs(60));
+        assert!(cached.lookup("down").is_err());
+        assert!(cached.lookup("down").is_err());
+        assert_eq!(cached.provider.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let cached = CachedProvider::new(CountingProvider::default(), Duration::from_secs(60))
+            .negative_ttl(Duration::from_secs(60))
+            .capacity(2);
+        let lookups = || cached.provider.0.load(Ordering::SeqCst);
+        cached.lookup("me").unwrap();
+        cached.lookup("a").unwrap();
+        cached.lookup("me").unwrap(); // "a" is now the least recently used
+        cached.lookup("b").unwrap();
+        assert_eq!(lookups(), 3);
+        assert_eq!(cached.cache.lock().unwrap().entries.len(), 2);
+
+        // "me" remains cached, but "a" was evicted
+        cached.lookup("me").unwrap();
+        assert_eq!(lookups(), 3);
+        cached.lookup("a").unwrap();
+        assert_eq!(lookups(), 4);
+    }
+}
@@ -0,0 +1,29 @@
+use std::fmt;
+use time::Timespec;
+
+/// A source of the current time, used for timestamp checks.  The clock is set with
+/// `HawkConfig::clock`, defaulting to `SystemClock`.
+pub trait Clock: fmt::Debug + Send + Sync + 'static {
+    /// Get the current time.
+    fn now(&self) -> Timespec;
+}
+
+/// A clock using the system time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timespec {
+        time::now().to_timespec()
+    }
+}
+
+/// A clock that always returns the same time, useful for deterministic tests.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub Timespec);
+
+impl Clock for FixedClock {
+    fn now(&self) -> Timespec {
+        self.0
+    }
+}
@@ -0,0 +1,452 @@
+use super::nonce::{spawn_purger, ReplayHook};
+use super::{
+    Clock, HawkError, NonceGenerator, NonceStore, RandomNonce, ReplayAttempt, ReplayKey,
+    SystemClock,
+};
+use hawk::Header;
+use rocket::fairing::AdHoc;
+use rocket::http::Method;
+use rocket::request::{Request, State};
+use rocket::Outcome;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use time::Duration;
+
+/// An optional Hawk header attribute, which can be made mandatory with `HawkConfig::require`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HawkField {
+    /// The payload hash (`hash`).
+    Hash,
+    /// Application-specific data (`ext`).
+    Ext,
+    /// The application id (`app`).
+    App,
+    /// The delegated-by application id (`dlg`).
+    Dlg,
+}
+
+impl HawkField {
+    fn is_present(self, header: &Header) -> bool {
+        match self {
+            HawkField::Hash => header.hash.is_some(),
+            HawkField::Ext => header.ext.is_some(),
+            HawkField::App => header.app.is_some(),
+            HawkField::Dlg => header.dlg.is_some(),
+        }
+    }
+}
+
+/// How a trailing slash on the request path is treated when calculating the MAC.  See
+/// `HawkConfig::trailing_slash`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Use the path as received.
+    Preserve,
+    /// Remove a trailing slash from any path other than `/`.
+    Strip,
+    /// Add a trailing slash to any path that lacks one.
+    Append,
+}
+
+/// What to do when a request's body and payload hash disagree in a way that some clients
+/// produce routinely.  See `HawkConfig::body_without_hash` and `HawkConfig::hash_without_body`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadMismatch {
+    /// Fail the request.
+    Reject,
+    /// Log a warning, then accept the request.
+    Warn,
+    /// Accept the request.
+    Ignore,
+}
+
+impl PayloadMismatch {
+    // Check whether a mismatch is allowed, logging the given message if warning.
+    pub(crate) fn allows(self, message: &str) -> bool {
+        match self {
+            PayloadMismatch::Reject => false,
+            PayloadMismatch::Warn => {
+                rocket::logger::warn(message);
+                true
+            }
+            PayloadMismatch::Ignore => true,
+        }
+    }
+}
+
+/// Configuration for the Hawk request guards.  To change the defaults, manage an instance of
+/// this type with `rocket.manage(..)`; if none is managed, the defaults apply.
+#[derive(Debug, Clone)]
+pub struct HawkConfig {
+    pub(crate) authorization_headers: Vec<String>,
+    pub(crate) lenient_scheme: bool,
+    pub(crate) select_hawk_header: bool,
+    pub(crate) default_port: u16,
+    pub(crate) include_query: bool,
+    pub(crate) normalize_percent_encoding: bool,
+    pub(crate) trailing_slash: TrailingSlash,
+    pub(crate) ts_skew: Duration,
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) nonce_generator: Arc<dyn NonceGenerator>,
+    pub(crate) nonce_store: Option<Arc<dyn NonceStore>>,
+    pub(crate) replay_key: ReplayKey,
+    pub(crate) unchecked_nonce_methods: Vec<Method>,
+    pub(crate) replay_hook: Option<ReplayHook>,
+    pub(crate) replays: Arc<AtomicU64>,
+    pub(crate) required_fields: Vec<(Option<Method>, HawkField)>,
+    pub(crate) require_payload_hash: bool,
+    pub(crate) body_without_hash: PayloadMismatch,
+    pub(crate) hash_without_body: PayloadMismatch,
+    pub(crate) max_ts_digits: Option<usize>,
+    pub(crate) max_nonce_len: Option<usize>,
+    pub(crate) nonce_charset: Option<String>,
+    pub(crate) tenant_header: Option<String>,
+}
+
+impl HawkConfig {
+    /// Set the ordered list of header names searched for the Hawk `Authorization` value.
+    /// Names are matched case-insensitively.  The first name in the list that is present on
+    /// the request takes precedence; if a later name is also present with a different value,
+    /// the request is rejected rather than guessing which one the client signed.
+    ///
+    /// The default is `["authorization"]`.
+    pub fn authorization_headers<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.authorization_headers = names.into_iter().map(|n| n.into()).collect();
+        self
+    }
+
+    /// Enable lenient parsing of the "Hawk" scheme, as hawk.js does: allowing any number of
+    /// spaces or tabs after the scheme, and an empty list of attributes.  The strict parsing
+    /// requires exactly one space.
+    ///
+    /// The default is strict parsing.
+    pub fn lenient_scheme(mut self, lenient: bool) -> Self {
+        self.lenient_scheme = lenient;
+        self
+    }
+
+    /// When a request carries several Authorization headers, ignore those that do not use the
+    /// "Hawk" scheme, such as a `Basic` header appended by a proxy.  It is still an error for
+    /// more than one Hawk header to be present.  This also applies to the alternative names set
+    /// with `authorization_headers`.
+    ///
+    /// The default is to reject requests with more than one Authorization header.
+    pub fn select_hawk_header(mut self, select: bool) -> Self {
+        self.select_hawk_header = select;
+        self
+    }
+
+    /// Set the port used in MAC calculations when the Host header does not specify one.  This
+    /// should be 443 when serving (or proxied from) HTTPS.
+    ///
+    /// The default is 80.
+    pub fn default_port(mut self, port: u16) -> Self {
+        self.default_port = port;
+        self
+    }
+
+    /// Include the query string in the URI used in MAC calculations.  Disable this if a proxy
+    /// rewrites query strings and clients sign only the path.
+    ///
+    /// The default is to include the query.
+    pub fn include_query(mut self, include: bool) -> Self {
+        self.include_query = include;
+        self
+    }
+
+    /// Normalize percent-encoding in the URI used in MAC calculations, as described in RFC
+    /// 3986 section 6.2.2: escaped unreserved characters (letters, digits, `-`, `.`, `_`, and
+    /// `~`) are decoded, and the hex digits of other escapes are uppercased.  Clients must
+    /// normalize their URIs in the same way.
+    ///
+    /// The default is to use the URI as received.
+    pub fn normalize_percent_encoding(mut self, normalize: bool) -> Self {
+        self.normalize_percent_encoding = normalize;
+        self
+    }
+
+    /// Set how a trailing slash on the request path is treated in MAC calculations, for
+    /// proxies that add or remove one.
+    ///
+    /// The default is `TrailingSlash::Preserve`.
+    pub fn trailing_slash(mut self, trailing_slash: TrailingSlash) -> Self {
+        self.trailing_slash = trailing_slash;
+        self
+    }
+
+    /// Set the maximum allowed difference between a header's timestamp and the current time.
+    /// Validated headers outside of this window fail with `HawkError::StaleTimestamp`.
+    ///
+    /// The default is 60 seconds.
+    pub fn ts_skew(mut self, ts_skew: Duration) -> Self {
+        self.ts_skew = ts_skew;
+        self
+    }
+
+    /// Set the clock used to check timestamps.
+    ///
+    /// The default is `SystemClock`.
+    pub fn clock<C: Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Set the generator of nonces for headers constructed by this crate.  Tests can use a
+    /// `SequentialNonce` for predictable headers.
+    ///
+    /// The default is `RandomNonce::new()`.
+    pub fn nonce_generator<G: NonceGenerator>(mut self, generator: G) -> Self {
+        self.nonce_generator = Arc::new(generator);
+        self
+    }
+
+    /// Generate a nonce with the configured generator.
+    pub fn generate_nonce(&self) -> String {
+        self.nonce_generator.generate()
+    }
+
+    /// Set the store used to reject requests that reuse a nonce, such as a `MemoryNonceStore`.
+    /// Requests are identified to the store as set with `replay_key`.
+    ///
+    /// The default is not to check nonces, leaving Hawk's replay protection to the timestamp
+    /// alone.
+    pub fn nonce_store<S: NonceStore>(mut self, store: S) -> Self {
+        self.nonce_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Set how requests are identified to the nonce store.  Validated headers without the
+    /// attributes used in the key fail with `HawkError::BadNonceFormat`.
+    ///
+    /// The default is `ReplayKey::Nonce`.
+    pub fn replay_key(mut self, replay_key: ReplayKey) -> Self {
+        self.replay_key = replay_key;
+        self
+    }
+
+    /// Do not check the nonces of requests with the given method, such as the idempotent `GET`
+    /// and `HEAD`, for which a replay is harmless.  Nonces of requests with other methods are
+    /// still checked.
+    pub fn skip_nonce_check_for(mut self, method: Method) -> Self {
+        self.unchecked_nonce_methods.push(method);
+        self
+    }
+
+    /// Set a function to be called with the details of each request rejected with
+    /// `HawkError::ReplayedNonce`.  It is called synchronously, so it should be fast.
+    pub fn on_replay<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ReplayAttempt) + Send + Sync + 'static,
+    {
+        self.replay_hook = Some(ReplayHook(Arc::new(hook)));
+        self
+    }
+
+    /// The number of requests rejected with `HawkError::ReplayedNonce` since this
+    /// configuration was created.  Get this from the managed `HawkConfig`, as with
+    /// `State<HawkConfig>` in a route.
+    pub fn replay_count(&self) -> u64 {
+        self.replays.load(Ordering::Relaxed)
+    }
+
+    /// A fairing that, at launch, starts a thread purging expired nonces from the nonce store of
+    /// the managed `HawkConfig` every interval, so that a long-running server does not hold
+    /// nonces from requests to idle parts of the store indefinitely.  The thread exits once the
+    /// store is dropped.
+    pub fn purge_fairing(interval: std::time::Duration) -> AdHoc {
+        AdHoc::on_launch("Hawk Nonce Purge", move |rocket| {
+            let store = rocket
+                .state::<HawkConfig>()
+                .and_then(|config| config.nonce_store.as_ref());
+            if let Some(store) = store {
+                spawn_purger(Arc::downgrade(store), interval);
+            }
+        })
+    }
+
+    /// Require the given attribute to be present in validated headers for all requests.
+    /// Headers without it fail with `HawkError::MissingField`.
+    pub fn require(mut self, field: HawkField) -> Self {
+        self.required_fields.push((None, field));
+        self
+    }
+
+    /// Require the given attribute to be present in validated headers for requests with the
+    /// given method, such as requiring `HawkField::Hash` for `Method::Post`.
+    pub fn require_for(mut self, method: Method, field: HawkField) -> Self {
+        self.required_fields.push((Some(method), field));
+        self
+    }
+
+    /// Require a payload hash in validated headers for requests that carry a body (POST, PUT,
+    /// and PATCH), so that clients cannot silently skip body integrity.  Headers without one
+    /// fail with `HawkError::MissingPayloadHash`.
+    ///
+    /// The default is to allow such requests without a hash.
+    pub fn require_payload_hash(mut self, require: bool) -> Self {
+        self.require_payload_hash = require;
+        self
+    }
+
+    /// Set what the `HawkVerifiedBody` guard and its relatives do with a non-empty body when
+    /// the header has no payload hash.  Rejected requests fail with
+    /// `HawkError::MissingPayloadHash`; others reach the route with an unverified body.
+    ///
+    /// The default is `PayloadMismatch::Reject`.
+    pub fn body_without_hash(mut self, action: PayloadMismatch) -> Self {
+        self.body_without_hash = action;
+        self
+    }
+
+    /// Set what the `HawkVerifiedBody` guard and its relatives do when the body is empty but
+    /// the header's payload hash does not match it, as from clients that hash a body they did
+    /// not send or hash empty bodies differently.  Rejected requests fail with
+    /// `HawkError::BadPayloadHash`.
+    ///
+    /// The default is `PayloadMismatch::Reject`.
+    pub fn hash_without_body(mut self, action: PayloadMismatch) -> Self {
+        self.hash_without_body = action;
+        self
+    }
+
+    /// Limit the `ts` attribute to a non-negative number with at most the given number of
+    /// digits.  Other headers fail with `HawkError::BadTimestampFormat` before any MAC
+    /// calculation.
+    ///
+    /// The default is no limit beyond what fits in a 64-bit integer.
+    pub fn max_ts_digits(mut self, digits: usize) -> Self {
+        self.max_ts_digits = Some(digits);
+        self
+    }
+
+    /// Limit the length of the `nonce` attribute, in bytes.  Longer nonces fail with
+    /// `HawkError::BadNonceFormat` before any MAC calculation.
+    ///
+    /// The default is no limit.
+    pub fn max_nonce_len(mut self, len: usize) -> Self {
+        self.max_nonce_len = Some(len);
+        self
+    }
+
+    /// Limit the `nonce` attribute to the characters in the given string, such as the
+    /// base64 alphabet.  Nonces containing other characters fail with
+    /// `HawkError::BadNonceFormat` before any MAC calculation.
+    ///
+    /// The default is to allow any characters.
+    pub fn nonce_charset<S: Into<String>>(mut self, chars: S) -> Self {
+        self.nonce_charset = Some(chars.into());
+        self
+    }
+
+    /// Take each request's tenant (see `HawkRequestInfo::tenant`) from the header with the
+    /// given name, such as `X-Tenant`, rather than from the Host header.  Requests without the
+    /// header have no tenant.
+    ///
+    /// The default is to use the host.
+    pub fn tenant_header<S: Into<String>>(mut self, name: S) -> Self {
+        self.tenant_header = Some(name.into());
+        self
+    }
+
+    /// Check whether nonces are checked for requests with the given method.
+    pub(crate) fn check_nonce_for(&self, method: Method) -> bool {
+        !self.unchecked_nonce_methods.contains(&method)
+    }
+
+    /// Count a replayed request, and report it to the replay hook, if any.
+    pub(crate) fn report_replay(&self, request: &Request, header: &Header) {
+        self.replays.fetch_add(1, Ordering::Relaxed);
+        if let Some(ReplayHook(ref hook)) = self.replay_hook {
+            hook(&ReplayAttempt {
+                id: header.id.as_deref().unwrap_or(""),
+                nonce: header.nonce.as_deref().unwrap_or(""),
+                client_ip: request.client_ip(),
+                method: request.method().as_str(),
+                uri: request.uri().to_string(),
+                route: request.route().map(|r| r.uri.to_string()),
+            });
+        }
+    }
+
+    /// Check the header's `ts` and `nonce` attributes against the configured format limits.
+    pub(crate) fn check_formats(&self, header: &Header) -> Result<(), HawkError> {
+        if let (Some(max_digits), Some(ts)) = (self.max_ts_digits, header.ts) {
+            if ts.sec < 0 || ts.sec.to_string().len() > max_digits {
+                return Err(HawkError::BadTimestampFormat);
+            }
+        }
+        if let Some(ref nonce) = header.nonce {
+            if let Some(max_len) = self.max_nonce_len {
+                if nonce.len() > max_len {
+                    return Err(HawkError::BadNonceFormat);
+                }
+            }
+            if let Some(ref charset) = self.nonce_charset {
+                if !nonce.chars().all(|c| charset.contains(c)) {
+                    return Err(HawkError::BadNonceFormat);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether a header without a payload hash is acceptable for the given method, per
+    /// `require_payload_hash`.
+    pub(crate) fn check_payload_hash(&self, method: Method, header: &Header) -> bool {
+        let bodied = matches!(method, Method::Post | Method::Put | Method::Patch);
+        !(self.require_payload_hash && bodied && header.hash.is_none())
+    }
+
+    /// Check that the header contains all fields required for the given method, returning the
+    /// first missing field otherwise.
+    pub(crate) fn check_required(&self, method: Method, header: &Header) -> Result<(), HawkField> {
+        for &(m, field) in &self.required_fields {
+            if m.map(|m| m == method).unwrap_or(true) && !field.is_present(header) {
+                return Err(field);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for HawkConfig {
+    fn default() -> Self {
+        HawkConfig {
+            authorization_headers: vec!["authorization".to_string()],
+            lenient_scheme: false,
+            select_hawk_header: false,
+            default_port: 80,
+            include_query: true,
+            normalize_percent_encoding: false,
+            trailing_slash: TrailingSlash::Preserve,
+            ts_skew: Duration::seconds(60),
+            clock: Arc::new(SystemClock),
+            nonce_generator: Arc::new(RandomNonce::new()),
+            nonce_store: None,
+            replay_key: ReplayKey::Nonce,
+            unchecked_nonce_methods: vec![],
+            replay_hook: None,
+            replays: Arc::new(AtomicU64::new(0)),
+            required_fields: vec![],
+            require_payload_hash: false,
+            body_without_hash: PayloadMismatch::Reject,
+            hash_without_body: PayloadMismatch::Reject,
+            max_ts_digits: None,
+            max_nonce_len: None,
+            nonce_charset: None,
+            tenant_header: None,
+        }
+    }
+}
+
+/// Get the managed HawkConfig, or a default configuration if none is managed.
+pub(crate) fn get_config<'a>(request: &'a Request) -> &'a HawkConfig {
+    match request.guard::<State<HawkConfig>>() {
+        Outcome::Success(config) => config.inner(),
+        _ => request.local_cache(HawkConfig::default),
+    }
+}
@@ -0,0 +1,289 @@
+use super::{
+    CredentialsProvider, HawkAlgorithm, HawkRequestInfo, MapProvider, ProviderError, RevocationList,
+};
+use hawk::Key;
+use rocket::fairing::AdHoc;
+use rocket::http::Method;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::Arc;
+use time::Duration;
+
+type AppValidator = dyn Fn(&str, Option<&str>) -> bool + Send + Sync;
+type DlgValidator = dyn Fn(&str, Option<&str>, Option<&str>) -> bool + Send + Sync;
+
+/// A single Hawk credential: a key, with its algorithm and any metadata and scopes.  Clones
+/// share the same key.
+#[derive(Clone)]
+pub struct HawkCredential {
+    pub(crate) key: Arc<Key>,
+    pub(crate) algorithm: Option<HawkAlgorithm>,
+    pub(crate) metadata: HashMap<String, String>,
+    pub(crate) scopes: HashSet<String>,
+    pub(crate) key_id: Option<String>,
+    pub(crate) allowed_algorithms: Option<Vec<HawkAlgorithm>>,
+    pub(crate) allowed_methods: Option<Vec<Method>>,
+    pub(crate) ts_skew: Option<Duration>,
+    pub(crate) require_hash: bool,
+    pub(crate) check_nonce: bool,
+    pub(crate) fingerprint: Option<String>,
+}
+
+impl HawkCredential {
+    /// Create a new credential with the given secret and algorithm.  Requests authenticated
+    /// with this credential must use the same algorithm.
+    pub fn new<B: Into<Vec<u8>>>(secret: B, algorithm: HawkAlgorithm) -> Self {
+        let secret = secret.into();
+        let fingerprint = fingerprint(&secret);
+        HawkCredential {
+            key: Arc::new(algorithm.key(secret)),
+            algorithm: Some(algorithm),
+            metadata: HashMap::new(),
+            scopes: HashSet::new(),
+            key_id: None,
+            allowed_algorithms: None,
+            allowed_methods: None,
+            ts_skew: None,
+            require_hash: false,
+            check_nonce: true,
+            fingerprint: Some(fingerprint),
+        }
+    }
+
+    /// Create a new credential from an existing key.  The key's algorithm cannot be
+    /// determined, so the algorithm reported for requests authenticated with it is inferred
+    /// from the length of the MAC.
+    pub fn from_key(key: Key) -> Self {
+        HawkCredential {
+            key: Arc::new(key),
+            algorithm: None,
+            metadata: HashMap::new(),
+            scopes: HashSet::new(),
+            key_id: None,
+            allowed_algorithms: None,
+            allowed_methods: None,
+            ts_skew: None,
+            require_hash: false,
+            check_nonce: true,
+            fingerprint: None,
+        }
+    }
+
+    /// The fingerprint of this credential's key, identifying it for `RevocationList::revoke_key`
+    /// without revealing the secret.  This is the hex-encoded first 16 bytes of the SHA-256
+    /// digest of the secret, and is not available for credentials created with `from_key`.
+    pub fn fingerprint(&self) -> Option<&str> {
+        self.fingerprint.as_deref()
+    }
+
+    /// Add a metadata value, which will be available from the `HawkPrincipal` of requests
+    /// authenticated with this credential.
+    pub fn metadata<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.metadata.insert(name.into(), value.into());
+        self
+    }
+
+    /// Label this credential's key, such as with the date it was issued.  When an id has
+    /// several keys during a rotation, the label of the key that verified a request is
+    /// available from the `HawkPrincipal`.
+    pub fn key_id<S: Into<String>>(mut self, key_id: S) -> Self {
+        self.key_id = Some(key_id.into());
+        self
+    }
+
+    /// Accept requests with this credential only if their MAC uses one of the given algorithms,
+    /// failing with `HawkError::AlgorithmNotAllowed` otherwise.  This is useful with
+    /// credentials created with `from_key`, for which the algorithm is inferred.
+    pub fn allow_algorithms<I: IntoIterator<Item = HawkAlgorithm>>(
+        mut self,
+        algorithms: I,
+    ) -> Self {
+        self.allowed_algorithms = Some(algorithms.into_iter().collect());
+        self
+    }
+
+    /// Accept requests with this credential only if they use one of the given methods,
+    /// failing with `HawkError::MethodNotAllowed` otherwise.
+    pub fn allow_methods<I: IntoIterator<Item = Method>>(mut self, methods: I) -> Self {
+        self.allowed_methods = Some(methods.into_iter().collect());
+        self
+    }
+
+    /// Set the maximum allowed timestamp skew for requests with this credential, overriding
+    /// `HawkConfig::ts_skew`.  If the route's `HawkPolicy` sets its own skew, the narrower of
+    /// the two applies, so a credential can tighten a policy but not relax it.
+    pub fn ts_skew(mut self, ts_skew: Duration) -> Self {
+        self.ts_skew = Some(ts_skew);
+        self
+    }
+
+    /// Require a payload hash in requests with this credential, failing with
+    /// `HawkError::MissingField` otherwise.
+    pub fn require_hash(mut self, require: bool) -> Self {
+        self.require_hash = require;
+        self
+    }
+
+    /// Check the nonces of requests with this credential against the
+    /// `HawkConfig::nonce_store`.  Disable this for trusted clients that cannot guarantee unique
+    /// nonces.
+    ///
+    /// The default is to check nonces.
+    pub fn check_nonce(mut self, check: bool) -> Self {
+        self.check_nonce = check;
+        self
+    }
+
+    /// Grant a scope, such as `"payments:write"`, to requests authenticated with this
+    /// credential.  Scopes are checked by the `HawkScoped` guard.
+    pub fn scope<S: Into<String>>(mut self, scope: S) -> Self {
+        self.scopes.insert(scope.into());
+        self
+    }
+}
+
+/// A set of Hawk credentials, mapping ids to keys.  Manage an instance of this type with
+/// `rocket.manage(..)` to make the credentials available to the `HawkAuthenticated` guard.
+///
+/// Credentials can be added directly, or looked up from a `CredentialsProvider`.
+#[derive(Default)]
+pub struct HawkCredentials {
+    keys: MapProvider,
+    provider: Option<Box<dyn CredentialsProvider>>,
+    revocations: RevocationList,
+    pub(crate) app_validator: Option<Box<AppValidator>>,
+    pub(crate) dlg_validator: Option<Box<DlgValidator>>,
+}
+
+impl HawkCredentials {
+    /// Create a new, empty set of credentials.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a credential with the given id and key, replacing any existing key for that id.
+    pub fn add<S: Into<String>>(self, id: S, key: Key) -> Self {
+        self.add_credential(id, HawkCredential::from_key(key))
+    }
+
+    /// Add a credential as for `add`, with metadata that will be available from the
+    /// `HawkPrincipal` of requests authenticated with it.
+    pub fn add_with_metadata<S: Into<String>>(
+        self,
+        id: S,
+        key: Key,
+        metadata: HashMap<String, String>,
+    ) -> Self {
+        let mut credential = HawkCredential::from_key(key);
+        credential.metadata = metadata;
+        self.add_credential(id, credential)
+    }
+
+    /// Add a credential with the given id, replacing any existing credential for that id.
+    pub fn add_credential<S: Into<String>>(mut self, id: S, credential: HawkCredential) -> Self {
+        self.keys.insert(id, credential);
+        self
+    }
+
+    /// A fairing that loads credentials from the `hawk_credentials` table in the Rocket
+    /// configuration (see `MapProvider::from_rocket_config`) and manages them as
+    /// `HawkCredentials`.  Invalid configuration is reported at launch, which is then
+    /// aborted.
+    pub fn fairing() -> AdHoc {
+        AdHoc::on_attach("Hawk Credentials", |rocket| {
+            let keys = MapProvider::from_rocket_config(rocket.config(), "hawk_credentials");
+            match from_keys(keys) {
+                Some(credentials) => Ok(rocket.manage(credentials)),
+                None => Err(rocket),
+            }
+        })
+    }
+
+    /// A fairing that loads credentials from the environment variable with the given name
+    /// (see `MapProvider::from_env`) and manages them as `HawkCredentials`.  A missing or
+    /// invalid variable is reported at launch, which is then aborted.
+    pub fn env_fairing(name: &'static str) -> AdHoc {
+        AdHoc::on_attach("Hawk Credentials", move |rocket| {
+            match from_keys(MapProvider::from_env(name)) {
+                Some(credentials) => Ok(rocket.manage(credentials)),
+                None => Err(rocket),
+            }
+        })
+    }
+
+    /// Set a provider to look up the credentials for ids that were not added directly.
+    pub fn provider<P: CredentialsProvider>(mut self, provider: P) -> Self {
+        self.provider = Some(Box::new(provider));
+        self
+    }
+
+    /// Set a function to check the header's `app` attribute after the MAC is verified.  It is
+    /// called with the id and the `app` value (if any), and should return false if that
+    /// application is not allowed for the id, causing a `HawkError::BadApp` failure.
+    ///
+    /// Note that the `app` and `dlg` attributes are not covered by the MAC.
+    pub fn app_validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&str, Option<&str>) -> bool + Send + Sync + 'static,
+    {
+        self.app_validator = Some(Box::new(validator));
+        self
+    }
+
+    /// Set a function to check the header's `dlg` attribute after the MAC is verified.  It is
+    /// called with the id, the `app` value (if any), and the `dlg` value (if any), and should
+    /// return false if the delegation is not allowed, causing a `HawkError::BadDlg` failure.
+    pub fn dlg_validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&str, Option<&str>, Option<&str>) -> bool + Send + Sync + 'static,
+    {
+        self.dlg_validator = Some(Box::new(validator));
+        self
+    }
+
+    /// The revocation list for these credentials.  Since `HawkCredentials` is managed state,
+    /// this can be reached from a route with `State<HawkCredentials>` to revoke credentials at
+    /// runtime.
+    pub fn revocations(&self) -> &RevocationList {
+        &self.revocations
+    }
+
+    /// Look up the active credentials for the given id, first among those added directly and
+    /// then from the provider.
+    pub(crate) fn get_all(
+        &self,
+        id: &str,
+        request: &HawkRequestInfo,
+    ) -> Result<Vec<HawkCredential>, ProviderError> {
+        let credentials = self.keys.lookup_all(id)?;
+        match self.provider {
+            Some(ref provider) if credentials.is_empty() => provider.lookup_for(id, request),
+            _ => Ok(credentials),
+        }
+    }
+}
+
+// Compute the fingerprint of a secret.
+fn fingerprint(secret: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, secret);
+    digest.as_ref()[..16]
+        .iter()
+        .fold(String::new(), |mut hex, b| {
+            let _ = write!(hex, "{:02x}", b);
+            hex
+        })
+}
+
+// Make HawkCredentials from the loaded keys, or log the error.
+fn from_keys(keys: Result<MapProvider, String>) -> Option<HawkCredentials> {
+    match keys {
+        Ok(keys) => Some(HawkCredentials {
+            keys,
+            ..HawkCredentials::default()
+        }),
+        Err(e) => {
+            rocket::logger::error(&format!("Invalid Hawk credentials: {}", e));
+            None
+        }
+    }
+}
@@ -1,12 +1,102 @@
+use super::HawkField;
 use hawk::Error;
 
-/// HawkError represents errors in parsing Authorization or ServerAuthorization headers.
+/// HawkError represents errors in parsing Authorization or ServerAuthorization headers, or in
+/// validating them.
 #[derive(Debug)]
 pub enum HawkError {
     /// No header was found, or a header was found but with the wrong scheme (that is, not "Hawk"),
-    /// or multiple headers were found.
+    /// or multiple or conflicting headers were found.
     NoHeader,
 
     /// A header was found, but parsing failed with the embedded error
     BadHawk(Error),
+
+    /// The header is missing an attribute required by the `HawkConfig`.
+    MissingField(HawkField),
+
+    /// The request has a body, but the header has no payload hash, as required by
+    /// `HawkConfig::require_payload_hash` or the `HawkVerifiedBody` guard.
+    MissingPayloadHash,
+
+    /// The header's `ts` attribute exceeds the limits set with `HawkConfig::max_ts_digits`.
+    BadTimestampFormat,
+
+    /// The header's `nonce` attribute exceeds the limits set with `HawkConfig::max_nonce_len`
+    /// or `HawkConfig::nonce_charset`, or is missing when a nonce store is configured.
+    BadNonceFormat,
+
+    /// No `HawkCredentials` are managed by the Rocket instance.
+    NoCredentials,
+
+    /// No validator of the type required by the `Hawk<V>` guard is managed by the Rocket
+    /// instance.
+    NoValidator,
+
+    /// The `HawkPolicy` requires a nonce check, but no `HawkConfig::nonce_store` is configured.
+    NoNonceStore,
+
+    /// The header's id was missing, or not found in the credentials.
+    UnknownId,
+
+    /// The request has no usable Host header, so the MAC cannot be calculated.
+    NoHost,
+
+    /// The header's id, or every key for that id, is in the `RevocationList`.
+    Revoked,
+
+    /// The header's MAC did not match that calculated for the request.
+    BadMac,
+
+    /// The header's MAC uses an algorithm not allowed for the credential.
+    AlgorithmNotAllowed,
+
+    /// The request's method is not allowed for the credential.
+    MethodNotAllowed,
+
+    /// The header's `app` attribute was rejected by the configured app validator.
+    BadApp,
+
+    /// The header's `dlg` attribute was rejected by the configured dlg validator.
+    BadDlg,
+
+    /// The request body did not match the header's payload hash.
+    BadPayloadHash,
+
+    /// The request body exceeded the "hawk" limit in the Rocket configuration.
+    BodyTooLarge,
+
+    /// The request body could not be read or converted, for the given reason.
+    BadBody(String),
+
+    /// The request body matched its payload hash, but could not be deserialized from JSON, for
+    /// the given reason.
+    BadJson(String),
+
+    /// The request body matched its payload hash, but could not be parsed as a form, for the
+    /// given reason.
+    BadForm(String),
+
+    /// The header's `ext` attribute could not be deserialized, for the given reason.
+    BadExt(String),
+
+    /// The request was authenticated, but its credentials lack the given scope.
+    MissingScope(String),
+
+    /// The header was authenticated, but its timestamp was outside of the allowed skew.  Per
+    /// the Hawk specification, the server may respond with a `WWW-Authenticate` header giving
+    /// its current time so the client can adjust.
+    StaleTimestamp,
+
+    /// The header was authenticated, but its nonce was already used, as recorded by the
+    /// `HawkConfig::nonce_store`.
+    ReplayedNonce,
+
+    /// The header was authenticated, but the `HawkConfig::nonce_store` could not check its
+    /// nonce, such as when the store's backing service is unavailable.
+    NonceStoreUnavailable,
+
+    /// The credentials provider could not look up the header's id, such as when its backing
+    /// service is unavailable.
+    ProviderUnavailable,
 }
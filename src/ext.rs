@@ -0,0 +1,141 @@
+use super::{HawkAuthenticated, HawkError};
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+use serde::de::DeserializeOwned;
+use std::ops::Deref;
+
+/// A request guard that authenticates the request as for `HawkAuthenticated`, then
+/// deserializes the header's `ext` attribute into `T`.
+///
+/// An `ext` value beginning with `{` is parsed as JSON; anything else is parsed as
+/// `key=value` pairs separated by `&`, as in a URL query.  A missing `ext` is treated as an
+/// empty set of pairs.  Values that cannot be deserialized fail with `HawkError::BadExt`.
+#[derive(Debug)]
+pub struct Ext<T>(T);
+
+impl<T> Ext<T> {
+    /// Get the deserialized value, consuming the guard.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<'a, 'r, T: DeserializeOwned> FromRequest<'a, 'r> for Ext<T> {
+    type Error = HawkError;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let hawk = request.guard::<HawkAuthenticated>()?;
+        match parse_ext(hawk.header().ext.as_ref().map(|e| &e[..]).unwrap_or("")) {
+            Ok(value) => Outcome::Success(Ext(value)),
+            Err(e) => Outcome::Failure((Status::BadRequest, e)),
+        }
+    }
+}
+
+impl<T> Deref for Ext<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Deserialize an `ext` value into `T`, using the same rules as the `Ext` guard.
+pub fn parse_ext<T: DeserializeOwned>(ext: &str) -> Result<T, HawkError> {
+    let ext = ext.trim();
+    if ext.starts_with('{') {
+        serde_json::from_str(ext).map_err(|e| HawkError::BadExt(e.to_string()))
+    } else {
+        serde_urlencoded::from_str(ext).map_err(|e| HawkError::BadExt(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_ext, Ext};
+    use crate::{HawkCredentials, HawkError};
+    use hawk::{Credentials, Key, RequestBuilder, SHA256};
+    use rocket::http::{Header, Status};
+    use rocket::local::Client;
+    use rocket::response::status;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Info {
+        user: String,
+        count: Option<u32>,
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let info: Info = parse_ext(r#"{"user": "amy", "count": 3}"#).unwrap();
+        assert_eq!(
+            info,
+            Info {
+                user: "amy".to_string(),
+                count: Some(3)
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_pairs() {
+        let info: Info = parse_ext("user=amy&count=3").unwrap();
+        assert_eq!(
+            info,
+            Info {
+                user: "amy".to_string(),
+                count: Some(3)
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        match parse_ext::<Info>("count=3") {
+            Err(HawkError::BadExt(_)) => {}
+            r => panic!("did not get BadExt: {:?}", r),
+        }
+        match parse_ext::<Info>("{\"user\": ") {
+            Err(HawkError::BadExt(_)) => {}
+            r => panic!("did not get BadExt: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_guard() {
+        #[get("/")]
+        fn index(ext: Result<Ext<Info>, HawkError>) -> status::Custom<String> {
+            match ext {
+                Ok(ext) => status::Custom(Status::Ok, ext.user.clone()),
+                Err(e) => status::Custom(Status::BadRequest, format!("{:?}", e)),
+            }
+        }
+
+        let key = || Key::new(vec![99u8; 32], &SHA256);
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: key(),
+        };
+        let header = RequestBuilder::new("GET", "localhost", 80, "/")
+            .ext("user=amy")
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+
+        let rocket = rocket::ignite()
+            .manage(HawkCredentials::new().add("me", key()))
+            .mount("/", routes![index]);
+        let client = Client::new(rocket).unwrap();
+        let mut res = client
+            .get("/")
+            .header(Header::new("Host", "localhost"))
+            .header(Header::new("Authorization", format!("Hawk {}", header)))
+            .dispatch();
+        assert_eq!(
+            (res.status(), res.body_string()),
+            (Status::Ok, Some("amy".to_string()))
+        );
+    }
+}
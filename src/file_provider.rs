@@ -0,0 +1,225 @@
+use super::{CredentialsProvider, HawkCredential, MapProvider, ProviderError};
+use rocket::config::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// A credentials provider that loads credentials from a file, reloading it when it changes so
+/// that keys can be added or revoked without restarting the application.
+///
+/// The file maps ids to tables with a base64-encoded `key`, an optional `algorithm`, and
+/// optional `scopes`, as for `MapProvider::from_rocket_config`.  Files with a `.json` extension
+/// are parsed as JSON, and all others as TOML.
+///
+/// The file's modification time is checked at most once per interval, during a lookup.  A
+/// changed file is loaded completely before replacing the previous credentials, so lookups
+/// never see a partially-loaded set.  If the changed file cannot be loaded, the error is logged
+/// and the previous credentials remain in use.
+pub struct FileProvider {
+    path: PathBuf,
+    interval: Duration,
+    state: RwLock<State>,
+}
+
+struct State {
+    credentials: Arc<MapProvider>,
+    modified: Option<SystemTime>,
+    checked: Instant,
+}
+
+impl FileProvider {
+    /// Load credentials from the file at the given path, returning an error describing the
+    /// problem if it cannot be loaded.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let modified = modified(&path);
+        let credentials = load(&path)?;
+        Ok(FileProvider {
+            path,
+            interval: Duration::from_secs(5),
+            state: RwLock::new(State {
+                credentials: Arc::new(credentials),
+                modified,
+                checked: Instant::now(),
+            }),
+        })
+    }
+
+    /// Set the minimum interval between checks for changes to the file.
+    ///
+    /// The default is 5 seconds.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Reload the file immediately, whether or not it has changed.  On error, the previous
+    /// credentials remain in use.
+    pub fn reload(&self) -> Result<(), String> {
+        let modified = modified(&self.path);
+        let credentials = load(&self.path)?;
+        let mut state = self.state.write().unwrap();
+        state.credentials = Arc::new(credentials);
+        state.modified = modified;
+        state.checked = Instant::now();
+        Ok(())
+    }
+
+    // Get the current credentials, first reloading the file if it is due for a check and has
+    // changed.
+    fn current(&self) -> Arc<MapProvider> {
+        {
+            let state = self.state.read().unwrap();
+            if state.checked.elapsed() < self.interval {
+                return state.credentials.clone();
+            }
+        }
+
+        let mut state = self.state.write().unwrap();
+        // another thread may have checked while this one waited for the lock
+        if state.checked.elapsed() >= self.interval {
+            state.checked = Instant::now();
+            let modified = modified(&self.path);
+            if modified != state.modified {
+                match load(&self.path) {
+                    Ok(credentials) => {
+                        state.credentials = Arc::new(credentials);
+                        state.modified = modified;
+                    }
+                    Err(e) => rocket::logger::error(&format!(
+                        "Could not reload Hawk credentials from {}: {}",
+                        self.path.display(),
+                        e
+                    )),
+                }
+            }
+        }
+        state.credentials.clone()
+    }
+}
+
+impl CredentialsProvider for FileProvider {
+    fn lookup(&self, id: &str) -> Result<Option<HawkCredential>, ProviderError> {
+        self.current().lookup(id)
+    }
+
+    fn lookup_all(&self, id: &str) -> Result<Vec<HawkCredential>, ProviderError> {
+        self.current().lookup_all(id)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Load credentials from the file at the given path.
+fn load(path: &Path) -> Result<MapProvider, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let value: Value = if path.extension().map(|e| e == "json").unwrap_or(false) {
+        serde_json::from_str(&contents).map_err(|e| e.to_string())?
+    } else {
+        contents.parse::<Value>().map_err(|e| e.to_string())?
+    };
+    match value.as_table() {
+        Some(table) => MapProvider::from_table(table),
+        None => Err("expected a table of credentials".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FileProvider;
+    use crate::CredentialsProvider;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::Duration;
+
+    // a path for a temporary credentials file, unique to the test
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rocket-hawk-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_toml() {
+        let path = temp_path("creds.toml");
+        fs::write(&path, "[me]\nkey = \"AQEB\"\nalgorithm = \"sha384\"\n").unwrap();
+        let provider = FileProvider::open(&path).unwrap();
+        assert!(provider.lookup("me").unwrap().is_some());
+        assert!(provider.lookup("you").unwrap().is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_json() {
+        let path = temp_path("creds.json");
+        fs::write(&path, r#"{"me": {"key": "AQEB", "scopes": ["read"]}}"#).unwrap();
+        let provider = FileProvider::open(&path).unwrap();
+        assert!(provider
+            .lookup("me")
+            .unwrap()
+            .unwrap()
+            .scopes
+            .contains("read"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid() {
+        let path = temp_path("invalid.json");
+        fs::write(&path, r#"{"me": {"key": "!!"}}"#).unwrap();
+        assert_eq!(
+            FileProvider::open(&path).err(),
+            Some("me: key: invalid base64".to_string())
+        );
+        fs::remove_file(&path).unwrap();
+        assert!(FileProvider::open(&path).is_err());
+    }
+
+    #[test]
+    fn test_reload() {
+        let path = temp_path("reload.json");
+        fs::write(&path, r#"{"me": {"key": "AQEB"}}"#).unwrap();
+        let provider = FileProvider::open(&path)
+            .unwrap()
+            .interval(Duration::from_secs(0));
+
+        // an invalid file leaves the previous credentials in place
+        fs::write(&path, "{").unwrap();
+        assert!(provider.reload().is_err());
+        assert!(provider.lookup("me").unwrap().is_some());
+
+        fs::write(&path, r#"{"you": {"key": "AQEB"}}"#).unwrap();
+        provider.reload().unwrap();
+        assert!(provider.lookup("me").unwrap().is_none());
+        assert!(provider.lookup("you").unwrap().is_some());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reload_on_change() {
+        let path = temp_path("changed.json");
+        fs::write(&path, r#"{"me": {"key": "AQEB"}}"#).unwrap();
+        let provider = FileProvider::open(&path)
+            .unwrap()
+            .interval(Duration::from_secs(0));
+        assert!(provider.lookup("you").unwrap().is_none());
+
+        // rewrite the file until its modification time changes, as a rewrite may land within
+        // the filesystem's timestamp resolution
+        let before = fs::metadata(&path).unwrap().modified().unwrap();
+        loop {
+            fs::write(&path, r#"{"you": {"key": "AQEB"}}"#).unwrap();
+            if fs::metadata(&path).unwrap().modified().unwrap() != before {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // the next lookup notices the change, without an explicit reload
+        assert!(provider.lookup("you").unwrap().is_some());
+        assert!(provider.lookup("me").unwrap().is_none());
+        fs::remove_file(&path).unwrap();
+    }
+}
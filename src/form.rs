@@ -0,0 +1,165 @@
+use super::{HawkError, HawkVerifiedBody};
+use rocket::data::{self, Data, FromData, FromDataSimple, Transform, Transformed};
+use rocket::http::Status;
+use rocket::request::{FormItems, FromForm, Request};
+use rocket::Outcome;
+use std::fmt;
+use std::ops::Deref;
+
+/// A data guard that verifies the request body against the header's payload hash, as for
+/// `HawkVerifiedBody`, then parses it as an `application/x-www-form-urlencoded` form into `T`,
+/// strictly, as for Rocket's `Form`.
+///
+/// Requests with another Content-Type are forwarded.  A body that does not match the hash fails
+/// with `HawkError::BadPayloadHash`, and one that matches but cannot be parsed into `T` with
+/// `HawkError::BadForm`.
+#[derive(Debug)]
+pub struct HawkForm<T>(T);
+
+impl<T> HawkForm<T> {
+    /// Get the parsed form, consuming the guard.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<'f, T> FromData<'f> for HawkForm<T>
+where
+    T: FromForm<'f>,
+    T::Error: fmt::Debug,
+{
+    type Error = HawkError;
+    type Owned = String;
+    type Borrowed = str;
+
+    fn transform(request: &Request, data: Data) -> Transform<data::Outcome<String, HawkError>> {
+        if !request.content_type().map_or(false, |ct| ct.is_form()) {
+            return Transform::Borrowed(Outcome::Forward(data));
+        }
+        let body = <HawkVerifiedBody<String> as FromDataSimple>::from_data(request, data);
+        Transform::Borrowed(body.map(HawkVerifiedBody::into_inner))
+    }
+
+    fn from_data(
+        _request: &Request,
+        outcome: Transformed<'f, Self>,
+    ) -> data::Outcome<Self, HawkError> {
+        let body = outcome.borrowed()?;
+        let mut items = FormItems::from(body);
+        let result = T::from_form(&mut items, true);
+        if !items.exhaust() {
+            let e = HawkError::BadForm("malformed form string".to_string());
+            return Outcome::Failure((Status::BadRequest, e));
+        }
+        match result {
+            Ok(form) => Outcome::Success(HawkForm(form)),
+            Err(e) => {
+                let e = HawkError::BadForm(format!("{:?}", e));
+                Outcome::Failure((Status::UnprocessableEntity, e))
+            }
+        }
+    }
+}
+
+impl<T> Deref for HawkForm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HawkForm;
+    use crate::{payload_hash, HawkAlgorithm, HawkAuthenticated, HawkCredentials, HawkError};
+    use hawk::{Credentials, Key, RequestBuilder, SHA256};
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::Client;
+    use rocket::response::status;
+
+    const KEY: [u8; 32] = [99u8; 32];
+
+    #[derive(Debug, FromForm)]
+    struct Item {
+        name: String,
+    }
+
+    #[post("/", data = "<item>")]
+    fn create(
+        _hawk: HawkAuthenticated,
+        item: Result<HawkForm<Item>, HawkError>,
+    ) -> status::Custom<String> {
+        match item {
+            Ok(item) => status::Custom(Status::Ok, item.name.clone()),
+            Err(e) => status::Custom(Status::BadRequest, format!("{:?}", e)),
+        }
+    }
+
+    #[post("/", data = "<body>", rank = 2)]
+    fn fallback(body: String) -> String {
+        format!("forwarded {}", body)
+    }
+
+    // post the given body, with a header carrying the hash of the given payload
+    fn post(content_type: ContentType, body: &str, hashed: &str) -> String {
+        let credentials = HawkCredentials::new().add("me", Key::new(KEY.to_vec(), &SHA256));
+        let client = Client::new(
+            rocket::ignite()
+                .manage(credentials)
+                .mount("/", routes![create, fallback]),
+        )
+        .unwrap();
+        let hash = payload_hash(
+            &content_type.to_string(),
+            hashed.as_bytes(),
+            HawkAlgorithm::Sha256,
+        );
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new(KEY.to_vec(), &SHA256),
+        };
+        let header = RequestBuilder::new("POST", "localhost", 8000, "/")
+            .hash(&hash[..])
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+        let mut res = client
+            .post("/")
+            .header(Header::new("Host", "localhost:8000"))
+            .header(Header::new("Authorization", format!("Hawk {}", header)))
+            .header(content_type)
+            .body(body)
+            .dispatch();
+        res.body_string().unwrap()
+    }
+
+    #[test]
+    fn test_valid() {
+        assert_eq!(
+            post(ContentType::Form, "name=widget", "name=widget"),
+            "widget"
+        );
+    }
+
+    #[test]
+    fn test_mismatch() {
+        assert_eq!(
+            post(ContentType::Form, "name=gadget", "name=widget"),
+            "BadPayloadHash"
+        );
+    }
+
+    #[test]
+    fn test_bad_form() {
+        assert!(post(ContentType::Form, "nom=widget", "nom=widget").starts_with("BadForm("));
+    }
+
+    #[test]
+    fn test_not_form() {
+        assert_eq!(
+            post(ContentType::Plain, "name=widget", "name=widget"),
+            "forwarded name=widget"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use super::config::get_config;
 use super::HawkError;
 use hawk::Header;
 use rocket::http::Status;
@@ -7,30 +8,48 @@ use std::ops::Deref;
 use std::str::FromStr;
 
 // A base type for the two public header types
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct AuthzHeader(Header);
 
-fn parse_header<'a, 'r>(
-    request: &'a Request<'r>,
-    header_name: &str,
+// The successfully-parsed Authorization header, cached for the duration of a request so that
+// several guards can share it; None if parsing failed.
+struct CachedAuthorization(Option<AuthzHeader>);
+
+fn parse_header<S: AsRef<str>>(
+    request: &Request,
+    header_names: &[S],
 ) -> request::Outcome<AuthzHeader, HawkError> {
-    // extract the header from the request, checking that there is exactly one
-    let hdrs: Vec<_> = request.headers().get(header_name).collect();
-    let hdr = match hdrs.len() {
-        0 => return Outcome::Failure((Status::Unauthorized, HawkError::NoHeader)),
-        1 => hdrs[0],
-        _ => return Outcome::Failure((Status::BadRequest, HawkError::NoHeader)),
+    let config = get_config(request);
+
+    // extract the header from the request, using the first of the given names that is present
+    // and checking that there is exactly one value for it.  Any other names that are present
+    // must carry the same value.
+    let mut found: Option<&str> = None;
+    for header_name in header_names {
+        let mut hdrs: Vec<_> = request.headers().get(header_name.as_ref()).collect();
+        if config.select_hawk_header {
+            // ignore values with other schemes, such as those added by proxies
+            hdrs.retain(|hdr| split_scheme(hdr, config.lenient_scheme).is_some());
+        }
+        let hdr = match hdrs.len() {
+            0 => continue,
+            1 => hdrs[0],
+            _ => return Outcome::Failure((Status::BadRequest, HawkError::NoHeader)),
+        };
+        match found {
+            None => found = Some(hdr),
+            Some(first) if first == hdr => {}
+            Some(_) => return Outcome::Failure((Status::BadRequest, HawkError::NoHeader)),
+        }
+    }
+    let hdr = match found {
+        Some(hdr) => hdr,
+        None => return Outcome::Failure((Status::Unauthorized, HawkError::NoHeader)),
     };
 
     // split 'Hawk <value>' (case-insensitive)
-    let hawk = match hdr.find(' ') {
-        Some(i) => {
-            if hdr[..i].eq_ignore_ascii_case("hawk") {
-                &hdr[i + 1..]
-            } else {
-                return Outcome::Failure((Status::Unauthorized, HawkError::NoHeader));
-            }
-        }
+    let hawk = match split_scheme(hdr, config.lenient_scheme) {
+        Some(hawk) => hawk,
         None => return Outcome::Failure((Status::Unauthorized, HawkError::NoHeader)),
     };
 
@@ -41,9 +60,36 @@ fn parse_header<'a, 'r>(
     }
 }
 
+// Split the scheme from a header value, returning the remainder if the scheme is "Hawk".  The
+// strict form requires exactly one space after the scheme; the lenient form, like hawk.js,
+// allows any run of spaces and tabs, or nothing at all.
+fn split_scheme(hdr: &str, lenient: bool) -> Option<&str> {
+    if lenient {
+        let is_space = |c: char| c == ' ' || c == '\t';
+        let hdr = hdr.trim_start_matches(is_space);
+        let i = hdr.find(is_space).unwrap_or(hdr.len());
+        if hdr[..i].eq_ignore_ascii_case("hawk") {
+            Some(hdr[i..].trim_start_matches(is_space))
+        } else {
+            None
+        }
+    } else {
+        let i = hdr.find(' ')?;
+        if hdr[..i].eq_ignore_ascii_case("hawk") {
+            Some(&hdr[i + 1..])
+        } else {
+            None
+        }
+    }
+}
+
 /// A request guard to require an "Authorization" header containing a syntactically valid Hawk
 /// value.  Note that it is up to the user to validate the header (perhaps by wrapping this
 /// type in another, application-specific request guard).
+///
+/// The header names searched can be configured with `HawkConfig::authorization_headers`.
+///
+/// The header is parsed only once per request, no matter how many guards use it.
 #[derive(Debug)]
 pub struct AuthorizationHeader(AuthzHeader);
 
@@ -51,7 +97,21 @@ impl<'a, 'r> FromRequest<'a, 'r> for AuthorizationHeader {
     type Error = HawkError;
 
     fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
-        parse_header(request, "authorization").map(|h| AuthorizationHeader(h))
+        let parse = || parse_header(request, &get_config(request).authorization_headers);
+        let mut failure = None;
+        let cached = request.local_cache(|| match parse() {
+            Outcome::Success(h) => CachedAuthorization(Some(h)),
+            outcome => {
+                failure = Some(outcome);
+                CachedAuthorization(None)
+            }
+        });
+        match (&cached.0, failure) {
+            (Some(h), _) => Outcome::Success(AuthorizationHeader(h.clone())),
+            (None, Some(outcome)) => outcome.map(AuthorizationHeader),
+            // parsing failed in an earlier guard; errors are not cached, so parse again
+            (None, None) => parse().map(AuthorizationHeader),
+        }
     }
 }
 
@@ -63,6 +123,48 @@ impl Deref for AuthorizationHeader {
     }
 }
 
+/// A request guard for routes where Hawk authentication is optional.  This succeeds with `None`
+/// when no Hawk "Authorization" header is present (including when a header with a different
+/// scheme is present), and with `Some` when a syntactically valid Hawk header is present.  It
+/// fails only when a Hawk header is present but cannot be parsed, or the header is ambiguous.
+///
+/// Unlike `Option<AuthorizationHeader>`, this does not silently treat a malformed header as
+/// absent.
+#[derive(Debug)]
+pub struct OptionalHawk(Option<AuthorizationHeader>);
+
+impl OptionalHawk {
+    /// Get the header, if any, consuming the guard.
+    pub fn into_inner(self) -> Option<AuthorizationHeader> {
+        self.0
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for OptionalHawk {
+    type Error = HawkError;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        match request.guard::<AuthorizationHeader>() {
+            Outcome::Success(h) => Outcome::Success(OptionalHawk(Some(h))),
+            // an absent header (or one with a different scheme) fails with Unauthorized, while
+            // multiple or conflicting headers fail with BadRequest
+            Outcome::Failure((Status::Unauthorized, HawkError::NoHeader)) => {
+                Outcome::Success(OptionalHawk(None))
+            }
+            Outcome::Failure(f) => Outcome::Failure(f),
+            Outcome::Forward(f) => Outcome::Forward(f),
+        }
+    }
+}
+
+impl Deref for OptionalHawk {
+    type Target = Option<AuthorizationHeader>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 /// Similar to `AuthorizationHeader`, but looking instead in the Hawk-specific
 /// "Servier-Authorization" header.
 #[derive(Debug)]
@@ -72,7 +174,7 @@ impl<'a, 'r> FromRequest<'a, 'r> for ServerAuthorizationHeader {
     type Error = HawkError;
 
     fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
-        parse_header(request, "server-authorization").map(|h| ServerAuthorizationHeader(h))
+        parse_header(request, &["server-authorization"]).map(ServerAuthorizationHeader)
     }
 }
 
@@ -86,17 +188,24 @@ impl Deref for ServerAuthorizationHeader {
 
 #[cfg(test)]
 mod test {
-    use super::{AuthorizationHeader, HawkError, ServerAuthorizationHeader};
+    use super::{
+        split_scheme, AuthorizationHeader, HawkError, OptionalHawk, ServerAuthorizationHeader,
+    };
+    use crate::HawkConfig;
     use rocket::http::{Header, Status};
     use rocket::local::{Client, LocalRequest};
     use rocket::response::status;
-    use rocket::Route;
+    use rocket::{Rocket, Route};
 
     const HEADER: &str = "id=\"xyz\", ts=\"1353832234\", nonce=\"abc\", mac=\"6R4rV5iE+NPoym+WwjeHzjAGXUtLNIxmo1vpMofpLAE=\"";
 
     // create a rocket and a client, then assert that the response is OK
     fn check_route(routes: Vec<Route>, setup_request: impl FnOnce(LocalRequest) -> LocalRequest) {
-        let rocket = rocket::ignite().mount("/", routes);
+        check_rocket(rocket::ignite().mount("/", routes), setup_request);
+    }
+
+    // like check_route, but with a pre-configured rocket
+    fn check_rocket(rocket: Rocket, setup_request: impl FnOnce(LocalRequest) -> LocalRequest) {
         let client = Client::new(rocket).unwrap();
         let mut res = setup_request(client.get("/")).dispatch();
         assert_eq!(
@@ -184,6 +293,63 @@ mod test {
         });
     }
 
+    #[test]
+    fn test_header_select_hawk() {
+        #[get("/")]
+        fn method(hawk: Result<AuthorizationHeader, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Ok(ref h) if (h.id == Some("xyz".to_string())) => {
+                    status::Custom(Status::Ok, "ok".to_string())
+                }
+                _ => status::Custom(Status::BadRequest, "did not get header".to_string()),
+            }
+        }
+
+        let config = HawkConfig::default().select_hawk_header(true);
+        let rocket = rocket::ignite().manage(config).mount("/", routes![method]);
+        check_rocket(rocket, |c| {
+            c.header(Header::new("Authorization", "Basic dXNlcjpwYXNz"))
+                .header(Header::new("Authorization", format!("Hawk {}", HEADER)))
+        });
+    }
+
+    #[test]
+    fn test_header_select_hawk_multiple() {
+        #[get("/")]
+        fn method(hawk: Result<AuthorizationHeader, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Err(HawkError::NoHeader) => status::Custom(Status::Ok, "ok".to_string()),
+                _ => status::Custom(Status::BadRequest, "did not get NoHeader".to_string()),
+            }
+        }
+
+        let config = HawkConfig::default().select_hawk_header(true);
+        let rocket = rocket::ignite().manage(config).mount("/", routes![method]);
+        check_rocket(rocket, |c| {
+            c.header(Header::new("Authorization", "Basic dXNlcjpwYXNz"))
+                .header(Header::new("Authorization", format!("Hawk {}", HEADER)))
+                .header(Header::new("Authorization", format!("Hawk {}", HEADER)))
+        });
+    }
+
+    #[test]
+    fn test_header_select_hawk_none() {
+        #[get("/")]
+        fn method(hawk: Result<OptionalHawk, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Ok(ref h) if h.is_none() => status::Custom(Status::Ok, "ok".to_string()),
+                _ => status::Custom(Status::BadRequest, "did not get None".to_string()),
+            }
+        }
+
+        let config = HawkConfig::default().select_hawk_header(true);
+        let rocket = rocket::ignite().manage(config).mount("/", routes![method]);
+        check_rocket(rocket, |c| {
+            c.header(Header::new("Authorization", "Basic dXNlcjpwYXNz"))
+                .header(Header::new("Authorization", "Bearer abc"))
+        });
+    }
+
     #[test]
     fn test_header_good_header() {
         #[get("/")]
@@ -201,6 +367,165 @@ mod test {
         });
     }
 
+    #[test]
+    fn test_header_alias() {
+        #[get("/")]
+        fn method(hawk: Result<AuthorizationHeader, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Ok(ref h) if (h.id == Some("xyz".to_string())) => {
+                    status::Custom(Status::Ok, "ok".to_string())
+                }
+                _ => status::Custom(Status::BadRequest, "did not get header".to_string()),
+            }
+        }
+
+        let config = HawkConfig::default().authorization_headers(vec!["x-authorization"]);
+        let rocket = rocket::ignite().manage(config).mount("/", routes![method]);
+        check_rocket(rocket, |c| {
+            c.header(Header::new("X-Authorization", format!("Hawk {}", HEADER)))
+        });
+    }
+
+    #[test]
+    fn test_header_alias_agreeing() {
+        #[get("/")]
+        fn method(hawk: Result<AuthorizationHeader, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Ok(ref h) if (h.id == Some("xyz".to_string())) => {
+                    status::Custom(Status::Ok, "ok".to_string())
+                }
+                _ => status::Custom(Status::BadRequest, "did not get header".to_string()),
+            }
+        }
+
+        let config =
+            HawkConfig::default().authorization_headers(vec!["authorization", "x-authorization"]);
+        let rocket = rocket::ignite().manage(config).mount("/", routes![method]);
+        check_rocket(rocket, |c| {
+            // an intermediary copied the header, so both are present with the same value
+            c.header(Header::new("Authorization", format!("Hawk {}", HEADER)))
+                .header(Header::new("X-Authorization", format!("Hawk {}", HEADER)))
+        });
+    }
+
+    #[test]
+    fn test_header_alias_conflict() {
+        #[get("/")]
+        fn method(hawk: Result<AuthorizationHeader, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Err(HawkError::NoHeader) => status::Custom(Status::Ok, "ok".to_string()),
+                _ => status::Custom(Status::BadRequest, "did not get NoHeader".to_string()),
+            }
+        }
+
+        let config =
+            HawkConfig::default().authorization_headers(vec!["authorization", "x-authorization"]);
+        let rocket = rocket::ignite().manage(config).mount("/", routes![method]);
+        check_rocket(rocket, |c| {
+            c.header(Header::new("Authorization", "Basic dXNlcjpwYXNz"))
+                .header(Header::new("X-Authorization", format!("Hawk {}", HEADER)))
+        });
+    }
+
+    #[test]
+    fn test_split_scheme_strict() {
+        assert_eq!(split_scheme("Hawk id=\"x\"", false), Some("id=\"x\""));
+        assert_eq!(split_scheme("hawk id=\"x\"", false), Some("id=\"x\""));
+        assert_eq!(split_scheme("Hawk  id=\"x\"", false), Some(" id=\"x\""));
+        assert_eq!(split_scheme("Hawk\tid=\"x\"", false), None);
+        assert_eq!(split_scheme("Hawk", false), None);
+        assert_eq!(split_scheme("Basic abc", false), None);
+    }
+
+    #[test]
+    fn test_split_scheme_lenient() {
+        assert_eq!(split_scheme("Hawk id=\"x\"", true), Some("id=\"x\""));
+        assert_eq!(split_scheme("Hawk  id=\"x\"", true), Some("id=\"x\""));
+        assert_eq!(split_scheme("Hawk\t id=\"x\"", true), Some("id=\"x\""));
+        assert_eq!(split_scheme("Hawk", true), Some(""));
+        assert_eq!(split_scheme("Hawkish id=\"x\"", true), None);
+        assert_eq!(split_scheme("Basic abc", true), None);
+    }
+
+    #[test]
+    fn test_header_lenient_double_space() {
+        #[get("/")]
+        fn method(hawk: Result<AuthorizationHeader, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Ok(ref h) if (h.id == Some("xyz".to_string())) => {
+                    status::Custom(Status::Ok, "ok".to_string())
+                }
+                _ => status::Custom(Status::BadRequest, "did not get header".to_string()),
+            }
+        }
+
+        let config = HawkConfig::default().lenient_scheme(true);
+        let rocket = rocket::ignite().manage(config).mount("/", routes![method]);
+        check_rocket(rocket, |c| {
+            c.header(Header::new("Authorization", format!("Hawk  {}", HEADER)))
+        });
+    }
+
+    #[test]
+    fn test_optional_absent() {
+        #[get("/")]
+        fn method(hawk: Result<OptionalHawk, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Ok(ref h) if h.is_none() => status::Custom(Status::Ok, "ok".to_string()),
+                _ => status::Custom(Status::BadRequest, "did not get None".to_string()),
+            }
+        }
+
+        check_route(routes![method], |c| c);
+    }
+
+    #[test]
+    fn test_optional_diff_scheme() {
+        #[get("/")]
+        fn method(hawk: Result<OptionalHawk, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Ok(ref h) if h.is_none() => status::Custom(Status::Ok, "ok".to_string()),
+                _ => status::Custom(Status::BadRequest, "did not get None".to_string()),
+            }
+        }
+
+        check_route(routes![method], |c| {
+            c.header(Header::new("Authorization", "bearer 123"))
+        });
+    }
+
+    #[test]
+    fn test_optional_bad_hawk() {
+        #[get("/")]
+        fn method(hawk: Result<OptionalHawk, HawkError>) -> status::Custom<String> {
+            match hawk {
+                Err(HawkError::BadHawk(_)) => status::Custom(Status::Ok, "ok".to_string()),
+                _ => status::Custom(Status::BadRequest, "did not get BadHawk".to_string()),
+            }
+        }
+
+        check_route(routes![method], |c| {
+            c.header(Header::new("Authorization", "Hawk nosuchfield=\"abc\""))
+        });
+    }
+
+    #[test]
+    fn test_optional_good_header() {
+        #[get("/")]
+        fn method(hawk: Result<OptionalHawk, HawkError>) -> status::Custom<String> {
+            match hawk.map(|h| h.into_inner()) {
+                Ok(Some(ref h)) if (h.id == Some("xyz".to_string())) => {
+                    status::Custom(Status::Ok, "ok".to_string())
+                }
+                _ => status::Custom(Status::BadRequest, "did not get header".to_string()),
+            }
+        }
+
+        check_route(routes![method], |c| {
+            c.header(Header::new("Authorization", format!("Hawk {}", HEADER)))
+        });
+    }
+
     // Just one test for ServerAuthorization, since it shares its implementation with
     // Authorization.
     #[test]
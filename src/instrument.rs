@@ -0,0 +1,166 @@
+use super::{CredentialsProvider, HawkCredential, HawkRequestInfo, ProviderError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// An event in the provider layer, reported to hooks installed with `InstrumentedProvider` or
+/// `CachedProvider::on_event`, such as to count lookups in a metrics system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProviderEvent<'a> {
+    /// A lookup of the given id is starting.
+    LookupStart { id: &'a str },
+
+    /// A lookup of the given id finished after the given time, finding the given number of
+    /// credentials.
+    LookupEnd {
+        id: &'a str,
+        elapsed: Duration,
+        found: usize,
+    },
+
+    /// A lookup found no credentials for the given id.
+    NotFound { id: &'a str },
+
+    /// A lookup of the given id failed after the given time, with the given error.
+    LookupFailed {
+        id: &'a str,
+        elapsed: Duration,
+        error: &'a ProviderError,
+    },
+
+    /// A `CachedProvider` found the given id in its cache.
+    CacheHit { id: &'a str },
+
+    /// A `CachedProvider` did not find the given id in its cache, or its entry had expired.
+    CacheMiss { id: &'a str },
+}
+
+pub(crate) type EventHook = dyn Fn(&ProviderEvent) + Send + Sync;
+
+/// A credentials provider that reports `LookupStart`, `LookupEnd`, `NotFound`, and
+/// `LookupFailed` events for each lookup in another provider to the given hook.  The hook is called synchronously, so it
+/// should be fast.
+///
+/// Wrap a `CachedProvider` to observe all lookups, or wrap the provider inside the cache to
+/// observe only those that reach the underlying store.
+pub struct InstrumentedProvider<P: CredentialsProvider> {
+    provider: P,
+    hook: Arc<EventHook>,
+}
+
+impl<P: CredentialsProvider> InstrumentedProvider<P> {
+    /// Wrap the given provider, reporting its events to the given hook.
+    pub fn new<F>(provider: P, hook: F) -> Self
+    where
+        F: Fn(&ProviderEvent) + Send + Sync + 'static,
+    {
+        InstrumentedProvider {
+            provider,
+            hook: Arc::new(hook),
+        }
+    }
+
+    fn instrument<F>(&self, id: &str, lookup: F) -> Result<Vec<HawkCredential>, ProviderError>
+    where
+        F: FnOnce() -> Result<Vec<HawkCredential>, ProviderError>,
+    {
+        (self.hook)(&ProviderEvent::LookupStart { id });
+        let start = Instant::now();
+        let credentials = match lookup() {
+            Ok(credentials) => credentials,
+            Err(error) => {
+                (self.hook)(&ProviderEvent::LookupFailed {
+                    id,
+                    elapsed: start.elapsed(),
+                    error: &error,
+                });
+                return Err(error);
+            }
+        };
+        (self.hook)(&ProviderEvent::LookupEnd {
+            id,
+            elapsed: start.elapsed(),
+            found: credentials.len(),
+        });
+        if credentials.is_empty() {
+            (self.hook)(&ProviderEvent::NotFound { id });
+        }
+        Ok(credentials)
+    }
+}
+
+impl<P: CredentialsProvider> CredentialsProvider for InstrumentedProvider<P> {
+    fn lookup(&self, id: &str) -> Result<Option<HawkCredential>, ProviderError> {
+        let credentials =
+            self.instrument(id, || Ok(self.provider.lookup(id)?.into_iter().collect()))?;
+        Ok(credentials.into_iter().next())
+    }
+
+    fn lookup_all(&self, id: &str) -> Result<Vec<HawkCredential>, ProviderError> {
+        self.instrument(id, || self.provider.lookup_all(id))
+    }
+
+    fn lookup_for(
+        &self,
+        id: &str,
+        request: &HawkRequestInfo,
+    ) -> Result<Vec<HawkCredential>, ProviderError> {
+        self.instrument(id, || self.provider.lookup_for(id, request))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InstrumentedProvider, ProviderEvent};
+    use crate::{CachedProvider, CredentialsProvider, HawkAlgorithm, HawkCredential, MapProvider};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    // a hook recording the names of the events it sees, returned with the recorded names
+    fn recorder() -> (
+        Arc<Mutex<Vec<String>>>,
+        impl Fn(&ProviderEvent) + Send + Sync + 'static,
+    ) {
+        let events = Arc::new(Mutex::new(vec![]));
+        let recorded = events.clone();
+        let hook = move |event: &ProviderEvent| {
+            let name = match event {
+                ProviderEvent::LookupStart { .. } => "start",
+                ProviderEvent::LookupEnd { .. } => "end",
+                ProviderEvent::NotFound { .. } => "not-found",
+                ProviderEvent::LookupFailed { .. } => "failed",
+                ProviderEvent::CacheHit { .. } => "hit",
+                ProviderEvent::CacheMiss { .. } => "miss",
+            };
+            recorded.lock().unwrap().push(name.to_string());
+        };
+        (events, hook)
+    }
+
+    fn provider() -> MapProvider {
+        MapProvider::new().add_credential(
+            "me",
+            HawkCredential::new(vec![1u8; 32], HawkAlgorithm::Sha256),
+        )
+    }
+
+    #[test]
+    fn test_instrumented() {
+        let (events, hook) = recorder();
+        let provider = InstrumentedProvider::new(provider(), hook);
+        assert!(provider.lookup("me").unwrap().is_some());
+        assert!(provider.lookup_all("you").unwrap().is_empty());
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec!["start", "end", "start", "end", "not-found"]
+        );
+    }
+
+    #[test]
+    fn test_cache_events() {
+        let (events, hook) = recorder();
+        let cached = CachedProvider::new(provider(), Duration::from_secs(60)).on_event(hook);
+        cached.lookup("me").unwrap();
+        cached.lookup("me").unwrap();
+        assert_eq!(*events.lock().unwrap(), vec!["miss", "hit"]);
+    }
+}
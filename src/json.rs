@@ -0,0 +1,120 @@
+use super::{HawkError, HawkVerifiedBody};
+use rocket::data::{self, Data, FromDataSimple};
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::Outcome;
+use serde::de::DeserializeOwned;
+use std::ops::Deref;
+
+/// A data guard that verifies the request body against the header's payload hash, as for
+/// `HawkVerifiedBody`, then deserializes it from JSON into `T`.
+///
+/// A body that does not match the hash fails with `HawkError::BadPayloadHash`, and one that
+/// matches but is not valid JSON for `T` with `HawkError::BadJson`.
+#[derive(Debug)]
+pub struct HawkJson<T>(T);
+
+impl<T> HawkJson<T> {
+    /// Get the deserialized value, consuming the guard.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: DeserializeOwned> FromDataSimple for HawkJson<T> {
+    type Error = HawkError;
+
+    fn from_data(request: &Request, data: Data) -> data::Outcome<Self, Self::Error> {
+        let body = HawkVerifiedBody::<Vec<u8>>::from_data(request, data)?.into_inner();
+        match serde_json::from_slice(&body) {
+            Ok(value) => Outcome::Success(HawkJson(value)),
+            Err(e) => Outcome::Failure((Status::BadRequest, HawkError::BadJson(e.to_string()))),
+        }
+    }
+}
+
+impl<T> Deref for HawkJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HawkJson;
+    use crate::{payload_hash, HawkAlgorithm, HawkAuthenticated, HawkCredentials, HawkError};
+    use hawk::{Credentials, Key, RequestBuilder, SHA256};
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::Client;
+    use rocket::response::status;
+    use serde::Deserialize;
+
+    const KEY: [u8; 32] = [99u8; 32];
+
+    #[derive(Debug, Deserialize)]
+    struct Item {
+        name: String,
+    }
+
+    #[post("/", data = "<item>")]
+    fn create(
+        _hawk: HawkAuthenticated,
+        item: Result<HawkJson<Item>, HawkError>,
+    ) -> status::Custom<String> {
+        match item {
+            Ok(item) => status::Custom(Status::Ok, item.name.clone()),
+            Err(e) => status::Custom(Status::BadRequest, format!("{:?}", e)),
+        }
+    }
+
+    // post the given body, with a header carrying the hash of the given payload
+    fn post(body: &str, hashed: &str) -> String {
+        let credentials = HawkCredentials::new().add("me", Key::new(KEY.to_vec(), &SHA256));
+        let client = Client::new(
+            rocket::ignite()
+                .manage(credentials)
+                .mount("/", routes![create]),
+        )
+        .unwrap();
+        let hash = payload_hash("application/json", hashed.as_bytes(), HawkAlgorithm::Sha256);
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new(KEY.to_vec(), &SHA256),
+        };
+        let header = RequestBuilder::new("POST", "localhost", 8000, "/")
+            .hash(&hash[..])
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+        let mut res = client
+            .post("/")
+            .header(Header::new("Host", "localhost:8000"))
+            .header(Header::new("Authorization", format!("Hawk {}", header)))
+            .header(ContentType::JSON)
+            .body(body)
+            .dispatch();
+        res.body_string().unwrap()
+    }
+
+    #[test]
+    fn test_valid() {
+        let body = r#"{"name": "widget"}"#;
+        assert_eq!(post(body, body), "widget");
+    }
+
+    #[test]
+    fn test_mismatch() {
+        assert_eq!(
+            post(r#"{"name": "gadget"}"#, r#"{"name": "widget"}"#),
+            "BadPayloadHash"
+        );
+    }
+
+    #[test]
+    fn test_bad_json() {
+        let body = r#"{"nom": "widget"}"#;
+        assert!(post(body, body).starts_with("BadJson("));
+    }
+}
@@ -3,8 +3,88 @@
 #[cfg(test)]
 #[macro_use]
 extern crate rocket;
+mod algorithm;
+mod authenticated;
+mod bloom_nonce;
+mod body;
+mod cache;
+mod clock;
+mod config;
+mod credentials;
 mod error;
+mod ext;
+mod file_provider;
+mod form;
 mod header;
+mod hkdf;
+mod instrument;
+mod json;
+#[cfg(feature = "memcached")]
+mod memcached_nonce;
+mod multipart;
+mod nonce;
+mod nonce_gen;
+mod policy;
+#[cfg(feature = "r2d2")]
+mod pool;
+mod principal;
+mod provider;
+#[cfg(feature = "redis")]
+mod redis_nonce;
+#[cfg(feature = "redis")]
+mod redis_provider;
+mod request_info;
+mod revocation;
+mod scope;
+#[cfg(feature = "sled")]
+mod sled_nonce;
+mod tenant;
+mod validator;
+#[cfg(feature = "vault")]
+mod vault_provider;
 
+pub use algorithm::HawkAlgorithm;
+pub use authenticated::HawkAuthenticated;
+pub use bloom_nonce::BloomNonceStore;
+pub use body::{
+    normalize_content_type, normalize_payload, payload_hash, FromHawkBody, HawkUpload,
+    HawkVerifiedBody, HawkVerifiedUpload, PayloadVerifier, VerifyingReader,
+};
+pub use cache::CachedProvider;
+pub use clock::{Clock, FixedClock, SystemClock};
+pub use config::{HawkConfig, HawkField, PayloadMismatch, TrailingSlash};
+pub use credentials::{HawkCredential, HawkCredentials};
 pub use error::HawkError;
-pub use header::{AuthorizationHeader, ServerAuthorizationHeader};
+pub use ext::{parse_ext, Ext};
+pub use file_provider::FileProvider;
+pub use form::HawkForm;
+pub use header::{AuthorizationHeader, OptionalHawk, ServerAuthorizationHeader};
+pub use hkdf::HkdfProvider;
+pub use instrument::{InstrumentedProvider, ProviderEvent};
+pub use json::HawkJson;
+#[cfg(feature = "memcached")]
+pub use memcached_nonce::MemcachedNonceStore;
+pub use multipart::{HawkMultipart, MultipartField};
+pub use nonce::{
+    MemoryNonceStore, NonceCheck, NonceStore, NonceStoreError, RecordingNonceStore, ReplayAttempt,
+    ReplayKey,
+};
+pub use nonce_gen::{NonceGenerator, RandomNonce, SequentialNonce};
+pub use policy::{DefaultPolicy, HawkPolicy, Lenient, Strict};
+#[cfg(feature = "r2d2")]
+pub use pool::PoolProvider;
+pub use principal::HawkPrincipal;
+pub use provider::{ChainProvider, CredentialsProvider, MapProvider, ProviderError};
+#[cfg(feature = "redis")]
+pub use redis_nonce::RedisNonceStore;
+#[cfg(feature = "redis")]
+pub use redis_provider::RedisProvider;
+pub use request_info::HawkRequestInfo;
+pub use revocation::RevocationList;
+pub use scope::{HawkScope, HawkScoped};
+#[cfg(feature = "sled")]
+pub use sled_nonce::SledNonceStore;
+pub use tenant::TenantProvider;
+pub use validator::{verify_rocket_request, Hawk, HawkValidator};
+#[cfg(feature = "vault")]
+pub use vault_provider::VaultProvider;
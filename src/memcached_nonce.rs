@@ -0,0 +1,238 @@
+use super::nonce::hash_key;
+use super::{NonceStore, NonceStoreError};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A nonce store in memcached, for applications that already run memcached for shared state.
+/// This is available with the `memcached` feature.
+///
+/// Each nonce is recorded with the memcached `add` command, which fails if the key already
+/// exists, expiring when it no longer needs to be retained.  Connections are reused between
+/// requests.  If memcached cannot be reached, requests fail with
+/// `HawkError::NonceStoreUnavailable`, since their nonces cannot be checked.
+pub struct MemcachedNonceStore {
+    address: String,
+    prefix: String,
+    connections: Mutex<Vec<BufReader<TcpStream>>>,
+}
+
+impl MemcachedNonceStore {
+    /// Create a store using the memcached server at the given address, such as
+    /// `127.0.0.1:11211`.
+    pub fn new<S: Into<String>>(address: S) -> Self {
+        MemcachedNonceStore {
+            address: address.into(),
+            prefix: "hawk-nonce:".to_string(),
+            connections: Mutex::new(vec![]),
+        }
+    }
+
+    /// Set the prefix of the memcached keys, such as to share a server between applications.
+    ///
+    /// The default is `hawk-nonce:`.
+    ///
+    /// Panics if the prefix contains whitespace or control characters, which the memcached
+    /// protocol does not allow in keys, or is so long that keys would exceed memcached's limit of
+    /// 250 bytes.
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        let prefix = prefix.into();
+        assert!(
+            !prefix.chars().any(|c| c.is_whitespace() || c.is_control()),
+            "memcached key prefix must not contain whitespace or control characters"
+        );
+        assert!(
+            prefix.len() + HASHED_KEY_LEN <= MAX_KEY_LEN,
+            "memcached key prefix must be at most {} bytes",
+            MAX_KEY_LEN - HASHED_KEY_LEN
+        );
+        self.prefix = prefix;
+        self
+    }
+
+    fn connect(&self) -> io::Result<BufReader<TcpStream>> {
+        let stream = TcpStream::connect(&self.address)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+        Ok(BufReader::new(stream))
+    }
+
+    // Send an `add` for the key, returning whether it was stored.
+    fn add(&self, key: &str, retain: Duration) -> io::Result<bool> {
+        let conn = self.connections.lock().unwrap().pop();
+        let mut conn = match conn {
+            Some(conn) => conn,
+            None => self.connect()?,
+        };
+        write!(
+            conn.get_mut(),
+            "add {} 0 {} 1\r\n1\r\n",
+            key,
+            expiry(retain, unix_now())
+        )?;
+        let mut reply = String::new();
+        conn.read_line(&mut reply)?;
+        let stored = match reply.trim_end() {
+            "STORED" => true,
+            "NOT_STORED" => false,
+            reply => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected reply {:?}", reply),
+                ))
+            }
+        };
+        // the connection is returned only after a complete exchange, so that a failed
+        // connection is not reused
+        self.connections.lock().unwrap().push(conn);
+        Ok(stored)
+    }
+}
+
+impl NonceStore for MemcachedNonceStore {
+    fn check_and_store(&self, key: &str, retain: Duration) -> Result<bool, NonceStoreError> {
+        // memcached keys are limited in length and may not contain spaces or control
+        // characters, so the key is hashed
+        self.add(&format!("{}{}", self.prefix, hash_key(key)), retain)
+            .map_err(|e| NonceStoreError(format!("could not record nonce in memcached: {}", e)))
+    }
+}
+
+// Memcached's limit on the length of keys, in bytes.
+const MAX_KEY_LEN: usize = 250;
+
+// The length of a key hashed with `hash_key`.
+const HASHED_KEY_LEN: usize = 64;
+
+// The longest expiry memcached treats as relative; longer values are taken as a Unix time.
+const MAX_RELATIVE_EXPIRY: u64 = 30 * 24 * 60 * 60;
+
+// The memcached expiry for a nonce retained for the given time: a number of seconds, or for
+// retentions longer than memcached allows, the absolute Unix time.
+fn expiry(retain: Duration, now: u64) -> u64 {
+    let secs = retain.as_secs().max(1);
+    if secs > MAX_RELATIVE_EXPIRY {
+        now + secs
+    } else {
+        secs
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl fmt::Debug for MemcachedNonceStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemcachedNonceStore")
+            .field("address", &self.address)
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{expiry, MemcachedNonceStore};
+    use crate::NonceStore;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    const RETAIN: Duration = Duration::from_secs(120);
+
+    #[test]
+    fn test_add() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        // a server storing each key once, on a single connection
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut keys = vec![];
+            let mut commands = vec![];
+            for _ in 0..3 {
+                let mut command = String::new();
+                let mut data = String::new();
+                reader.read_line(&mut command).unwrap();
+                reader.read_line(&mut data).unwrap();
+                let key = command.split(' ').nth(1).unwrap().to_string();
+                let reply = if keys.contains(&key) {
+                    "NOT_STORED"
+                } else {
+                    "STORED"
+                };
+                keys.push(key);
+                write!(writer, "{}\r\n", reply).unwrap();
+                commands.push(command);
+            }
+            commands
+        });
+
+        let store = MemcachedNonceStore::new(address);
+        assert_eq!(store.check_and_store("a", RETAIN), Ok(true));
+        assert_eq!(store.check_and_store("a", RETAIN), Ok(false));
+        assert_eq!(store.check_and_store("b a\r\n", RETAIN), Ok(true));
+
+        let commands = server.join().unwrap();
+        assert!(commands[0].starts_with("add hawk-nonce:"));
+        assert!(commands[0].ends_with(" 0 120 1\r\n"));
+        // the key is hashed
+        assert_eq!(commands[2].len(), "add hawk-nonce: 0 120 1\r\n".len() + 64);
+    }
+
+    #[test]
+    fn test_unavailable() {
+        // nothing listens on a port reserved and then released
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let store = MemcachedNonceStore::new(address);
+        assert!(store.check_and_store("a", RETAIN).is_err());
+    }
+
+    #[test]
+    fn test_expiry() {
+        let now = 1_600_000_000;
+        assert_eq!(expiry(RETAIN, now), 120);
+        assert_eq!(expiry(Duration::from_secs(0), now), 1);
+        let thirty_days = Duration::from_secs(30 * 24 * 60 * 60);
+        assert_eq!(expiry(thirty_days, now), 2_592_000);
+        // longer retentions would be read as a time in 1970, so an absolute time is sent
+        let sixty_days = thirty_days * 2;
+        assert_eq!(expiry(sixty_days, now), now + 5_184_000);
+    }
+
+    #[test]
+    fn test_prefix() {
+        let store = MemcachedNonceStore::new("127.0.0.1:11211").prefix("a".repeat(186));
+        assert_eq!(store.prefix.len(), 186);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_prefix_space() {
+        MemcachedNonceStore::new("127.0.0.1:11211").prefix("my app:");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_prefix_newline() {
+        MemcachedNonceStore::new("127.0.0.1:11211").prefix("app\r\nflush_all\r\n");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_prefix_too_long() {
+        MemcachedNonceStore::new("127.0.0.1:11211").prefix("a".repeat(187));
+    }
+}
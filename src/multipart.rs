@@ -0,0 +1,261 @@
+use super::FromHawkBody;
+use rocket::request::Request;
+use std::io;
+
+/// A `multipart/form-data` request body, for use as `HawkVerifiedBody<HawkMultipart>`.  The
+/// payload hash is checked against the raw body as received, and only then is the body parsed
+/// into fields.
+///
+/// The whole body is held in memory, up to the "hawk" limit in the Rocket configuration.
+#[derive(Debug, Clone)]
+pub struct HawkMultipart {
+    fields: Vec<MultipartField>,
+}
+
+/// A single field of a `HawkMultipart` body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipartField {
+    /// The field name, from the part's Content-Disposition.
+    pub name: String,
+
+    /// The file name, from the part's Content-Disposition, if this is a file upload.
+    pub filename: Option<String>,
+
+    /// The part's Content-Type, if given.
+    pub content_type: Option<String>,
+
+    /// The part's content.
+    pub data: Vec<u8>,
+}
+
+impl HawkMultipart {
+    /// Parse a `multipart/form-data` body with the given Content-Type header value, which
+    /// must include the boundary.
+    pub fn parse(content_type: &str, body: &[u8]) -> Result<Self, String> {
+        let boundary = boundary(content_type)
+            .ok_or_else(|| "Content-Type is not multipart/form-data with a boundary".to_string())?;
+        let delimiter = format!("--{}", boundary).into_bytes();
+
+        let mut pos = find(body, &delimiter, 0).ok_or("missing multipart boundary")?;
+        let mut fields = vec![];
+        loop {
+            pos += delimiter.len();
+            if body[pos..].starts_with(b"--") {
+                return Ok(HawkMultipart { fields });
+            }
+            if !body[pos..].starts_with(b"\r\n") {
+                return Err("malformed multipart boundary".to_string());
+            }
+            pos += 2;
+
+            let headers_end = find(body, b"\r\n\r\n", pos).ok_or("unterminated part headers")?;
+            let headers = std::str::from_utf8(&body[pos..headers_end])
+                .map_err(|_| "part headers are not UTF-8".to_string())?;
+            pos = headers_end + 4;
+
+            let mut close = b"\r\n".to_vec();
+            close.extend_from_slice(&delimiter);
+            let end = find(body, &close, pos).ok_or("unterminated multipart part")?;
+            fields.push(field(headers, body[pos..end].to_vec())?);
+            pos = end + 2;
+        }
+    }
+
+    /// All fields, in the order they appeared in the body.
+    pub fn fields(&self) -> &[MultipartField] {
+        &self.fields
+    }
+
+    /// The first field with the given name.
+    pub fn field(&self, name: &str) -> Option<&MultipartField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// The content of the first field with the given name, if it is valid UTF-8.
+    pub fn text(&self, name: &str) -> Option<&str> {
+        self.field(name)
+            .and_then(|f| std::str::from_utf8(&f.data).ok())
+    }
+}
+
+impl FromHawkBody for HawkMultipart {
+    type Buffer = Vec<u8>;
+
+    fn buffer(_request: &Request) -> io::Result<Self::Buffer> {
+        Ok(vec![])
+    }
+
+    fn from_body(request: &Request, body: Vec<u8>) -> Result<Self, String> {
+        let content_type = request.headers().get_one("Content-Type").unwrap_or("");
+        HawkMultipart::parse(content_type, &body)
+    }
+}
+
+// Find the boundary parameter of a multipart/form-data Content-Type.
+fn boundary(content_type: &str) -> Option<&str> {
+    let mut params = content_type.split(';');
+    if !params
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        return None;
+    }
+    params
+        .filter_map(param)
+        .find(|(name, _)| name.eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value)
+        .filter(|b| !b.is_empty())
+}
+
+// Split a `name=value` header parameter, removing any quotes from the value.
+fn param(param: &str) -> Option<(&str, &str)> {
+    let mut split = param.splitn(2, '=');
+    let name = split.next()?.trim();
+    let value = split.next()?.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+    Some((name, value))
+}
+
+// Build a field from a part's headers and content.
+fn field(headers: &str, data: Vec<u8>) -> Result<MultipartField, String> {
+    let mut name = None;
+    let mut filename = None;
+    let mut content_type = None;
+    for line in headers.split("\r\n") {
+        let mut split = line.splitn(2, ':');
+        let header = split.next().unwrap_or("").trim();
+        let value = split.next().unwrap_or("").trim();
+        if header.eq_ignore_ascii_case("Content-Disposition") {
+            for (param_name, param_value) in value.split(';').skip(1).filter_map(param) {
+                match &param_name.to_ascii_lowercase()[..] {
+                    "name" => name = Some(param_value.to_string()),
+                    "filename" => filename = Some(param_value.to_string()),
+                    _ => {}
+                }
+            }
+        } else if header.eq_ignore_ascii_case("Content-Type") {
+            content_type = Some(value.to_string());
+        }
+    }
+    Ok(MultipartField {
+        name: name.ok_or("multipart part has no name")?,
+        filename,
+        content_type,
+        data,
+    })
+}
+
+// Find the first occurrence of needle in haystack at or after from.
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| i + from)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{boundary, HawkMultipart, MultipartField};
+    use crate::{payload_hash, HawkAlgorithm, HawkAuthenticated, HawkCredentials};
+    use crate::{HawkError, HawkVerifiedBody};
+    use hawk::{Credentials, Key, RequestBuilder, SHA256};
+    use rocket::http::{Header, Status};
+    use rocket::local::Client;
+    use rocket::response::status;
+
+    const KEY: [u8; 32] = [99u8; 32];
+    const CONTENT_TYPE: &str = "multipart/form-data; boundary=XyZ";
+    const BODY: &[u8] = b"preamble\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+        Hello\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+        Content-Type: text/plain\r\n\r\n\
+        line one\r\nline two\r\n--XyZ--\r\n";
+
+    #[test]
+    fn test_boundary() {
+        assert_eq!(boundary(CONTENT_TYPE), Some("XyZ"));
+        assert_eq!(
+            boundary("Multipart/Form-Data; charset=utf-8; boundary=\"a b\""),
+            Some("a b")
+        );
+        assert_eq!(boundary("multipart/mixed; boundary=XyZ"), None);
+        assert_eq!(boundary("multipart/form-data"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let multipart = HawkMultipart::parse(CONTENT_TYPE, BODY).unwrap();
+        assert_eq!(multipart.text("title"), Some("Hello"));
+        assert_eq!(
+            multipart.field("file"),
+            Some(&MultipartField {
+                name: "file".to_string(),
+                filename: Some("a.txt".to_string()),
+                content_type: Some("text/plain".to_string()),
+                data: b"line one\r\nline two".to_vec(),
+            })
+        );
+        assert_eq!(multipart.fields().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert!(HawkMultipart::parse(CONTENT_TYPE, b"--XyZ\r\nno end").is_err());
+        assert!(HawkMultipart::parse(CONTENT_TYPE, b"no boundary").is_err());
+        assert!(HawkMultipart::parse("text/plain", BODY).is_err());
+    }
+
+    #[test]
+    fn test_guard() {
+        #[post("/", data = "<body>")]
+        fn upload(
+            _hawk: HawkAuthenticated,
+            body: Result<HawkVerifiedBody<HawkMultipart>, HawkError>,
+        ) -> status::Custom<String> {
+            match body {
+                Ok(body) => status::Custom(Status::Ok, body.text("title").unwrap().to_string()),
+                Err(e) => status::Custom(Status::BadRequest, format!("{:?}", e)),
+            }
+        }
+
+        let credentials = HawkCredentials::new().add("me", Key::new(KEY.to_vec(), &SHA256));
+        let client = Client::new(
+            rocket::ignite()
+                .manage(credentials)
+                .mount("/", routes![upload]),
+        )
+        .unwrap();
+        let post = |body: &[u8]| {
+            // the hash covers the raw body, with the boundary removed from the content type
+            let hash = payload_hash(CONTENT_TYPE, BODY, HawkAlgorithm::Sha256);
+            let credentials = Credentials {
+                id: "me".to_string(),
+                key: Key::new(KEY.to_vec(), &SHA256),
+            };
+            let header = RequestBuilder::new("POST", "localhost", 8000, "/")
+                .hash(&hash[..])
+                .request()
+                .make_header(&credentials)
+                .unwrap();
+            client
+                .post("/")
+                .header(Header::new("Host", "localhost:8000"))
+                .header(Header::new("Authorization", format!("Hawk {}", header)))
+                .header(Header::new("Content-Type", CONTENT_TYPE))
+                .body(body)
+                .dispatch()
+                .body_string()
+                .unwrap()
+        };
+
+        assert_eq!(post(BODY), "Hello");
+        let tampered = String::from_utf8_lossy(BODY).replace("Hello", "Howdy");
+        assert_eq!(post(tampered.as_bytes()), "BadPayloadHash");
+    }
+}
@@ -0,0 +1,412 @@
+use hawk::Header;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::fmt;
+use std::hash::BuildHasher;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A record of the nonces used in authenticated requests, providing replay protection.  The
+/// store is set with `HawkConfig::nonce_store`, and consulted only for requests that are
+/// otherwise valid, so that forged requests cannot use up nonces.  Requests reusing a nonce fail
+/// with `HawkError::ReplayedNonce`, and those whose nonce the store cannot check, such as when
+/// its backing service is down, with `HawkError::NonceStoreUnavailable`.
+///
+/// Each request is identified to the store by a key, built from its id, timestamp, and nonce,
+/// or from its MAC, as set with `HawkConfig::replay_key`.
+///
+/// Stores need only remember nonces until their timestamps fall outside of the allowed skew, as
+/// requests replayed after that fail with `HawkError::StaleTimestamp` before the store is
+/// consulted.  A request may be stamped up to the skew in the future, so the guard asks stores to
+/// retain each nonce for twice the skew that applies to the request, whether from the
+/// `HawkConfig`, a `HawkPolicy`, or the credential.
+pub trait NonceStore: fmt::Debug + Send + Sync + 'static {
+    /// Record the given key, to be retained for the given time, returning false if it was
+    /// already recorded, or an error if the store could not tell.  This must be atomic, so that
+    /// only one of several concurrent requests with the same key succeeds.
+    fn check_and_store(&self, key: &str, retain: Duration) -> Result<bool, NonceStoreError>;
+
+    /// Discard expired nonces, freeing their memory.  This is called periodically by the
+    /// fairing from `HawkConfig::purge_fairing`.
+    ///
+    /// The default does nothing, for stores that expire nonces themselves.
+    fn purge(&self) {}
+}
+
+/// A failure of a `NonceStore` to check a key, with a description of the problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonceStoreError(pub String);
+
+impl fmt::Display for NonceStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The parts of a request that identify it to a `NonceStore`, set with `HawkConfig::replay_key`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayKey {
+    /// The id, timestamp, and nonce, so that a nonce may be reused with a different id or
+    /// timestamp, as the Hawk specification allows.  This is the default.
+    Nonce,
+
+    /// The MAC, which covers the timestamp, the nonce, and the request itself, for client
+    /// fleets that legitimately reuse nonces in different requests.
+    Mac,
+}
+
+impl ReplayKey {
+    /// Build the key for the given header, returning None if it lacks the necessary attributes.
+    /// Nonce keys include the id's length, so that ids containing `:` cannot collide with other
+    /// ids' nonces.
+    pub(crate) fn key(self, header: &Header) -> Option<String> {
+        match self {
+            ReplayKey::Nonce => {
+                let (id, ts, nonce) = (header.id.as_ref()?, header.ts?, header.nonce.as_ref()?);
+                Some(format!("{}:{}:{}:{}", id.len(), id, ts.sec, nonce))
+            }
+            ReplayKey::Mac => Some(format!("mac:{}", base64::encode(&header.mac.as_ref()?[..]))),
+        }
+    }
+}
+
+/// A request rejected with `HawkError::ReplayedNonce`, reported to the hook set with
+/// `HawkConfig::on_replay`, such as for intrusion detection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayAttempt<'a> {
+    /// The header's id.
+    pub id: &'a str,
+    /// The header's nonce.
+    pub nonce: &'a str,
+    /// The client's IP address, if known.
+    pub client_ip: Option<IpAddr>,
+    /// The request method, such as `GET`.
+    pub method: &'a str,
+    /// The request URI.
+    pub uri: String,
+    /// The URI pattern of the matched route, if any, such as `/items/<id>`.
+    pub route: Option<String>,
+}
+
+// A hook for replay attempts, which can be debug-formatted as part of a HawkConfig.
+#[derive(Clone)]
+pub(crate) struct ReplayHook(pub(crate) Arc<dyn Fn(&ReplayAttempt) + Send + Sync>);
+
+impl fmt::Debug for ReplayHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ReplayHook")
+    }
+}
+
+/// Hash a key into 64 hex digits, for stores that limit the length or characters of their keys,
+/// or should not reveal them.
+#[cfg(any(feature = "memcached", feature = "sled"))]
+pub(crate) fn hash_key(key: &str) -> String {
+    let hash = ring::digest::digest(&ring::digest::SHA256, key.as_bytes());
+    hash.as_ref().iter().fold(String::new(), |mut hex, b| {
+        hex.push_str(&format!("{:02x}", b));
+        hex
+    })
+}
+
+/// An in-memory nonce store.  This provides replay protection for a single instance of an
+/// application; for several instances behind a load balancer, use a shared store.
+///
+/// Nonces are divided among a number of shards, each with its own lock, so that concurrent
+/// requests rarely contend for the same lock.
+#[derive(Debug)]
+pub struct MemoryNonceStore {
+    hasher: RandomState,
+    shards: Vec<Mutex<State>>,
+}
+
+// The nonces, with an index by expiration time.
+#[derive(Debug, Default)]
+struct State {
+    seen: HashSet<String>,
+    expiry: BTreeSet<(Instant, String)>,
+}
+
+impl MemoryNonceStore {
+    /// Create a new, empty store.
+    pub fn new() -> Self {
+        MemoryNonceStore {
+            hasher: RandomState::new(),
+            shards: (0..16).map(|_| Mutex::new(State::default())).collect(),
+        }
+    }
+
+    /// Set the number of shards.  More shards reduce lock contention between concurrent
+    /// requests, at a small cost in memory.
+    ///
+    /// The default is 16.
+    pub fn shards(mut self, shards: usize) -> Self {
+        self.shards = (0..shards.max(1))
+            .map(|_| Mutex::new(State::default()))
+            .collect();
+        self
+    }
+}
+
+impl Default for MemoryNonceStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NonceStore for MemoryNonceStore {
+    fn check_and_store(&self, key: &str, retain: Duration) -> Result<bool, NonceStoreError> {
+        let now = Instant::now();
+        let shard = self.hasher.hash_one(key) as usize % self.shards.len();
+        let mut state = self.shards[shard].lock().unwrap();
+        state.expire(now);
+
+        if !state.seen.insert(key.to_string()) {
+            return Ok(false);
+        }
+        state.expiry.insert((now + retain, key.to_string()));
+        Ok(true)
+    }
+
+    fn purge(&self) {
+        let now = Instant::now();
+        for shard in &self.shards {
+            shard.lock().unwrap().expire(now);
+        }
+    }
+}
+
+impl State {
+    // Discard the nonces that expired before the given time.
+    fn expire(&mut self, now: Instant) {
+        while self.expiry.first().map(|e| e.0 <= now).unwrap_or(false) {
+            if let Some((_, key)) = self.expiry.pop_first() {
+                self.seen.remove(&key);
+            }
+        }
+    }
+}
+
+/// A nonce store for tests, recording every check made against it.  Responses can be
+/// injected with `respond`; otherwise it behaves like a `MemoryNonceStore`, accepting each key
+/// once.
+///
+/// Clones share their recording, so a clone can be given to `HawkConfig::nonce_store` and
+/// the original inspected once requests are made.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingNonceStore {
+    inner: Arc<Mutex<Recording>>,
+}
+
+#[derive(Debug, Default)]
+struct Recording {
+    checks: Vec<NonceCheck>,
+    responses: VecDeque<Result<bool, NonceStoreError>>,
+    store: MemoryNonceStore,
+}
+
+/// A check made against a `RecordingNonceStore`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonceCheck {
+    /// The key checked, as determined by `HawkConfig::replay_key`.
+    pub key: String,
+
+    /// The time the key was to be retained.
+    pub retain: Duration,
+
+    /// Whether the key was accepted as new; false if it was rejected as a replay or the check
+    /// failed.
+    pub accepted: bool,
+}
+
+impl RecordingNonceStore {
+    /// Create a new store, with no checks recorded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a response for the next check that has no earlier queued response: `true` to
+    /// accept the key as new, or `false` to reject it as a replay.  Keys checked with a queued
+    /// response are not stored.
+    pub fn respond(&self, accepted: bool) {
+        self.inner.lock().unwrap().responses.push_back(Ok(accepted));
+    }
+
+    /// Queue a failure with the given description for the next check that has no earlier
+    /// queued response, as from a store whose backing service is unavailable.
+    pub fn fail<S: Into<String>>(&self, error: S) {
+        let error = NonceStoreError(error.into());
+        self.inner.lock().unwrap().responses.push_back(Err(error));
+    }
+
+    /// The checks made so far, in order.
+    pub fn checks(&self) -> Vec<NonceCheck> {
+        self.inner.lock().unwrap().checks.clone()
+    }
+
+    /// Forget all checks, queued responses, and stored keys.
+    pub fn reset(&self) {
+        *self.inner.lock().unwrap() = Recording::default();
+    }
+}
+
+impl NonceStore for RecordingNonceStore {
+    fn check_and_store(&self, key: &str, retain: Duration) -> Result<bool, NonceStoreError> {
+        let mut inner = self.inner.lock().unwrap();
+        let result = match inner.responses.pop_front() {
+            Some(result) => result,
+            None => inner.store.check_and_store(key, retain),
+        };
+        inner.checks.push(NonceCheck {
+            key: key.to_string(),
+            retain,
+            accepted: result == Ok(true),
+        });
+        result
+    }
+}
+
+// Start a thread purging the store every interval, until the store is dropped.
+pub(crate) fn spawn_purger(store: Weak<dyn NonceStore>, interval: Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        match store.upgrade() {
+            Some(store) => store.purge(),
+            None => return,
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        spawn_purger, MemoryNonceStore, NonceCheck, NonceStore, NonceStoreError,
+        RecordingNonceStore, ReplayKey,
+    };
+    use hawk::Header;
+    use std::sync::{Arc, Weak};
+    use std::thread;
+    use std::time::Duration;
+
+    const RETAIN: Duration = Duration::from_secs(120);
+
+    #[test]
+    fn test_replay_key() {
+        let header: Header = "id=\"me\", ts=\"1000\", nonce=\"abc\", mac=\"AQID\""
+            .parse()
+            .unwrap();
+        assert_eq!(
+            ReplayKey::Nonce.key(&header),
+            Some("2:me:1000:abc".to_string())
+        );
+        assert_eq!(ReplayKey::Mac.key(&header), Some("mac:AQID".to_string()));
+
+        let header: Header = "id=\"me\", mac=\"AQID\"".parse().unwrap();
+        assert_eq!(ReplayKey::Nonce.key(&header), None);
+    }
+
+    #[test]
+    fn test_replay() {
+        let store = MemoryNonceStore::new();
+        assert_eq!(store.check_and_store("a", RETAIN), Ok(true));
+        assert_eq!(store.check_and_store("a", RETAIN), Ok(false));
+        assert_eq!(store.check_and_store("b", RETAIN), Ok(true));
+    }
+
+    #[test]
+    fn test_shards() {
+        let store = MemoryNonceStore::new().shards(4);
+        for i in 0..100 {
+            assert_eq!(store.check_and_store(&i.to_string(), RETAIN), Ok(true));
+        }
+        for i in 0..100 {
+            assert_eq!(store.check_and_store(&i.to_string(), RETAIN), Ok(false));
+        }
+        // the keys are spread among the shards
+        let used = store
+            .shards
+            .iter()
+            .filter(|s| !s.lock().unwrap().seen.is_empty())
+            .count();
+        assert!(used > 1);
+    }
+
+    #[test]
+    fn test_purge() {
+        let store = Arc::new(MemoryNonceStore::new());
+        store.check_and_store("a", Duration::from_secs(0)).unwrap();
+        let stored = || -> usize {
+            store
+                .shards
+                .iter()
+                .map(|s| s.lock().unwrap().seen.len())
+                .sum()
+        };
+        assert_eq!(stored(), 1);
+
+        let weak: Weak<MemoryNonceStore> = Arc::downgrade(&store);
+        spawn_purger(weak, Duration::from_millis(1));
+        for _ in 0..1000 {
+            if stored() == 0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(stored(), 0);
+    }
+
+    #[test]
+    fn test_expiry() {
+        let store = MemoryNonceStore::new();
+        assert_eq!(store.check_and_store("long", RETAIN), Ok(true));
+        assert_eq!(store.check_and_store("a", Duration::from_secs(0)), Ok(true));
+        assert_eq!(store.check_and_store("a", Duration::from_secs(0)), Ok(true));
+        // keys retained for longer are not expired with those stored after them
+        assert_eq!(store.check_and_store("long", RETAIN), Ok(false));
+        let stored: usize = store
+            .shards
+            .iter()
+            .map(|s| s.lock().unwrap().seen.len())
+            .sum();
+        assert!(stored <= 2);
+    }
+
+    #[test]
+    fn test_recording() {
+        let store = RecordingNonceStore::new();
+        let retain = Duration::from_secs(60);
+        store.clone().respond(false);
+        assert_eq!(store.check_and_store("a", retain), Ok(false));
+        assert_eq!(store.check_and_store("a", retain), Ok(true));
+        assert_eq!(store.check_and_store("a", retain), Ok(false));
+        store.respond(true);
+        assert_eq!(store.check_and_store("a", retain), Ok(true));
+        store.fail("down");
+        assert_eq!(
+            store.check_and_store("a", retain),
+            Err(NonceStoreError("down".to_string()))
+        );
+
+        let check = |accepted| NonceCheck {
+            key: "a".to_string(),
+            retain,
+            accepted,
+        };
+        assert_eq!(
+            store.checks(),
+            vec![
+                check(false),
+                check(true),
+                check(false),
+                check(true),
+                check(false)
+            ]
+        );
+
+        store.reset();
+        assert!(store.checks().is_empty());
+        assert_eq!(store.check_and_store("a", retain), Ok(true));
+    }
+}
@@ -0,0 +1,144 @@
+use ring::rand::{SecureRandom, SystemRandom};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A source of nonces for headers constructed by this crate.  The generator is set with
+/// `HawkConfig::nonce_generator`, defaulting to `RandomNonce`.
+pub trait NonceGenerator: fmt::Debug + Send + Sync + 'static {
+    /// Generate a new nonce.
+    fn generate(&self) -> String;
+}
+
+const ALPHANUMERIC: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// A generator of cryptographically random nonces, drawn uniformly from an alphabet.
+#[derive(Debug, Clone)]
+pub struct RandomNonce {
+    len: usize,
+    alphabet: Vec<char>,
+}
+
+impl RandomNonce {
+    pub fn new() -> Self {
+        RandomNonce {
+            len: 16,
+            alphabet: ALPHANUMERIC.chars().collect(),
+        }
+    }
+
+    /// Set the length of generated nonces, in characters.
+    ///
+    /// The default is 16.
+    pub fn len(mut self, len: usize) -> Self {
+        self.len = len;
+        self
+    }
+
+    /// Set the characters used in generated nonces.  Repeated characters are more likely to
+    /// appear.  This should agree with any `HawkConfig::nonce_charset` of the receiving server.
+    ///
+    /// The default is ASCII letters and digits.
+    ///
+    /// Panics if the alphabet is empty or has more than 256 characters.
+    pub fn alphabet<S: AsRef<str>>(mut self, alphabet: S) -> Self {
+        let alphabet: Vec<char> = alphabet.as_ref().chars().collect();
+        assert!(
+            !alphabet.is_empty() && alphabet.len() <= 256,
+            "nonce alphabet must have 1 to 256 characters"
+        );
+        self.alphabet = alphabet;
+        self
+    }
+}
+
+impl Default for RandomNonce {
+    fn default() -> Self {
+        RandomNonce::new()
+    }
+}
+
+impl NonceGenerator for RandomNonce {
+    fn generate(&self) -> String {
+        let rng = SystemRandom::new();
+        let n = self.alphabet.len();
+        // reject bytes beyond the largest multiple of the alphabet size, so that each character
+        // is equally likely
+        let limit = 256 - 256 % n;
+        let mut nonce = String::with_capacity(self.len);
+        let mut count = 0;
+        let mut buf = [0u8; 32];
+        while count < self.len {
+            rng.fill(&mut buf)
+                .expect("system random number generator failed");
+            for &b in buf.iter().filter(|&&b| (b as usize) < limit) {
+                if count == self.len {
+                    break;
+                }
+                nonce.push(self.alphabet[b as usize % n]);
+                count += 1;
+            }
+        }
+        nonce
+    }
+}
+
+/// A generator of predictable nonces, `<prefix>0`, `<prefix>1`, and so on, useful for
+/// deterministic tests.  Never use this in production.
+#[derive(Debug)]
+pub struct SequentialNonce {
+    prefix: String,
+    next: AtomicU64,
+}
+
+impl SequentialNonce {
+    pub fn new<S: Into<String>>(prefix: S) -> Self {
+        SequentialNonce {
+            prefix: prefix.into(),
+            next: AtomicU64::new(0),
+        }
+    }
+}
+
+impl NonceGenerator for SequentialNonce {
+    fn generate(&self) -> String {
+        format!(
+            "{}{}",
+            self.prefix,
+            self.next.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NonceGenerator, RandomNonce, SequentialNonce};
+
+    #[test]
+    fn test_random() {
+        let gen = RandomNonce::new();
+        let one = gen.generate();
+        assert_eq!(one.len(), 16);
+        assert!(one.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(one, gen.generate());
+    }
+
+    #[test]
+    fn test_random_alphabet() {
+        let nonce = RandomNonce::new().len(100).alphabet("xé").generate();
+        assert_eq!(nonce.chars().count(), 100);
+        assert!(nonce.chars().all(|c| c == 'x' || c == 'é'));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_random_empty_alphabet() {
+        RandomNonce::new().alphabet("");
+    }
+
+    #[test]
+    fn test_sequential() {
+        let gen = SequentialNonce::new("n");
+        assert_eq!(gen.generate(), "n0");
+        assert_eq!(gen.generate(), "n1");
+    }
+}
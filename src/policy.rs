@@ -0,0 +1,99 @@
+use time::Duration;
+
+/// A policy selects, at compile time, how strictly a request guard verifies Hawk headers, so
+/// that different routes can use different settings: for example `HawkAuthenticated<Strict>`
+/// for write endpoints and `HawkAuthenticated<Lenient>` for reads.  Policies are zero-sized
+/// marker types; define your own by implementing this trait and overriding the constants.
+///
+/// Anything a policy does not override is taken from the `HawkConfig`.
+pub trait HawkPolicy: Send + Sync + 'static {
+    /// The maximum allowed timestamp skew in seconds, overriding `HawkConfig::ts_skew`.  A
+    /// credential's own skew is narrowed to this, but never widened.
+    const TS_SKEW_SECONDS: Option<i64> = None;
+
+    /// Whether to check nonces against the `HawkConfig::nonce_store`.  `Some(true)` checks them
+    /// even where the configuration or the credential would skip the check, and fails with
+    /// `HawkError::NoNonceStore` if no store is configured; `Some(false)` never checks them; and
+    /// None follows the configuration.
+    const CHECK_NONCE: Option<bool> = None;
+
+    /// If true, headers must include a payload hash, in addition to any fields required by the
+    /// `HawkConfig`.
+    const REQUIRE_HASH: bool = false;
+
+    /// The timestamp skew for this policy, given the configured skew.
+    fn ts_skew(configured: Duration) -> Duration {
+        Self::TS_SKEW_SECONDS
+            .map(Duration::seconds)
+            .unwrap_or(configured)
+    }
+
+    /// The timestamp skew for this policy, given the configured skew and any skew for the
+    /// credential, which overrides the configured skew but not a narrower policy skew.
+    fn ts_skew_for(configured: Duration, credential: Option<Duration>) -> Duration {
+        match (credential, Self::TS_SKEW_SECONDS) {
+            (Some(credential), Some(policy)) => credential.min(Duration::seconds(policy)),
+            (Some(credential), None) => credential,
+            (None, _) => Self::ts_skew(configured),
+        }
+    }
+}
+
+/// The policy used when none is given, applying the `HawkConfig` unchanged.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultPolicy;
+
+impl HawkPolicy for DefaultPolicy {}
+
+/// A strict policy, suitable for write endpoints: timestamps may be at most 30 seconds off, a
+/// payload hash is required, and nonces are always checked, so a nonce store must be
+/// configured.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Strict;
+
+impl HawkPolicy for Strict {
+    const TS_SKEW_SECONDS: Option<i64> = Some(30);
+    const REQUIRE_HASH: bool = true;
+    const CHECK_NONCE: Option<bool> = Some(true);
+}
+
+/// A lenient policy, suitable for read endpoints: timestamps may be up to five minutes off.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lenient;
+
+impl HawkPolicy for Lenient {
+    const TS_SKEW_SECONDS: Option<i64> = Some(300);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DefaultPolicy, HawkPolicy, Lenient, Strict};
+    use time::Duration;
+
+    #[test]
+    fn test_ts_skew() {
+        let configured = Duration::seconds(60);
+        assert_eq!(DefaultPolicy::ts_skew(configured), configured);
+        assert_eq!(Strict::ts_skew(configured), Duration::seconds(30));
+        assert_eq!(Lenient::ts_skew(configured), Duration::seconds(300));
+    }
+
+    #[test]
+    fn test_ts_skew_for_credential() {
+        let configured = Duration::seconds(60);
+        let credential = Some(Duration::seconds(120));
+        assert_eq!(DefaultPolicy::ts_skew_for(configured, None), configured);
+        assert_eq!(
+            DefaultPolicy::ts_skew_for(configured, credential),
+            Duration::seconds(120)
+        );
+        assert_eq!(
+            Strict::ts_skew_for(configured, credential),
+            Duration::seconds(30)
+        );
+        assert_eq!(
+            Lenient::ts_skew_for(configured, credential),
+            Duration::seconds(120)
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use super::{CredentialsProvider, HawkCredential, ProviderError};
+use r2d2::{ManageConnection, Pool};
+use std::error::Error;
+
+type Query<C> =
+    dyn Fn(&mut C, &str) -> Result<Option<HawkCredential>, Box<dyn Error>> + Send + Sync;
+
+/// A credentials provider that looks up credentials with a user-supplied query, run on a
+/// connection from an r2d2 pool.  This is available with the `r2d2` feature.
+///
+/// This works with the connection managers used by `rocket_contrib::databases`, such as
+/// Diesel's `ConnectionManager<PgConnection>`; build a pool for the database with
+/// `rocket_contrib::databases::database_config` and `Poolable::pool`.  Wrap the provider in a
+/// `CachedProvider` to avoid a query for every request.
+///
+/// Failures to get a connection or run the query are returned as a `ProviderError`, so that the
+/// request fails with `HawkError::ProviderUnavailable` rather than as an unknown id.
+pub struct PoolProvider<M: ManageConnection> {
+    pool: Pool<M>,
+    query: Box<Query<M::Connection>>,
+}
+
+impl<M: ManageConnection> PoolProvider<M> {
+    /// Create a provider using connections from the given pool.  The query is called with a
+    /// connection and the id, and should return the credential for the id, if it is known.
+    pub fn new<F>(pool: Pool<M>, query: F) -> Self
+    where
+        F: Fn(&mut M::Connection, &str) -> Result<Option<HawkCredential>, Box<dyn Error>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        PoolProvider {
+            pool,
+            query: Box::new(query),
+        }
+    }
+}
+
+impl<M: ManageConnection> CredentialsProvider for PoolProvider<M> {
+    fn lookup(&self, id: &str) -> Result<Option<HawkCredential>, ProviderError> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| ProviderError(format!("could not get a connection: {}", e)))?;
+        (self.query)(&mut conn, id).map_err(|e| ProviderError(format!("query failed: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PoolProvider;
+    use crate::{CredentialsProvider, HawkAlgorithm, HawkCredential, ProviderError};
+    use r2d2::{ManageConnection, Pool};
+    use std::collections::HashMap;
+    use std::fmt;
+
+    // a "database" connection holding a fixed table of secrets
+    struct Connection(HashMap<&'static str, Vec<u8>>);
+
+    #[derive(Debug)]
+    struct NoError;
+
+    impl fmt::Display for NoError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "no error")
+        }
+    }
+
+    impl std::error::Error for NoError {}
+
+    struct Manager;
+
+    impl ManageConnection for Manager {
+        type Connection = Connection;
+        type Error = NoError;
+
+        fn connect(&self) -> Result<Connection, NoError> {
+            let mut table = HashMap::new();
+            table.insert("me", vec![1u8; 32]);
+            table.insert("broken", vec![]);
+            Ok(Connection(table))
+        }
+
+        fn is_valid(&self, _conn: &mut Connection) -> Result<(), NoError> {
+            Ok(())
+        }
+
+        fn has_broken(&self, _conn: &mut Connection) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_pool_provider() {
+        let pool = Pool::builder().max_size(1).build(Manager).unwrap();
+        let provider = PoolProvider::new(pool, |conn: &mut Connection, id| match conn.0.get(id) {
+            Some(secret) if secret.is_empty() => Err("empty secret".into()),
+            Some(secret) => Ok(Some(HawkCredential::new(
+                secret.clone(),
+                HawkAlgorithm::Sha256,
+            ))),
+            None => Ok(None),
+        });
+        assert!(provider.lookup("me").unwrap().is_some());
+        assert!(provider.lookup("you").unwrap().is_none());
+        assert_eq!(
+            provider.lookup("broken").err(),
+            Some(ProviderError("query failed: empty secret".to_string()))
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use super::{HawkAlgorithm, HawkCredential, PayloadVerifier};
+use hawk::Header;
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+use time::{Duration, Timespec};
+
+/// The authenticated identity of a caller, as produced by the `HawkAuthenticated` guard.  This
+/// dereferences to the id.
+#[derive(Debug, Clone)]
+pub struct HawkPrincipal {
+    id: String,
+    algorithm: HawkAlgorithm,
+    ts: Timespec,
+    metadata: HashMap<String, String>,
+    scopes: HashSet<String>,
+    key_id: Option<String>,
+    pub(crate) ts_skew: Option<Duration>,
+    pub(crate) check_nonce: bool,
+    header: Header,
+}
+
+impl HawkPrincipal {
+    pub(crate) fn new(
+        id: String,
+        algorithm: HawkAlgorithm,
+        credential: HawkCredential,
+        header: Header,
+    ) -> Self {
+        HawkPrincipal {
+            id,
+            algorithm,
+            // a validated header always has a timestamp
+            ts: header.ts.unwrap_or_else(|| Timespec::new(0, 0)),
+            metadata: credential.metadata,
+            scopes: credential.scopes,
+            key_id: credential.key_id,
+            ts_skew: credential.ts_skew,
+            check_nonce: credential.check_nonce,
+            header,
+        }
+    }
+
+    /// The id of the authenticated credentials.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The algorithm of the key that verified the request's MAC.
+    pub fn algorithm(&self) -> HawkAlgorithm {
+        self.algorithm
+    }
+
+    /// The timestamp from the request's header.
+    pub fn ts(&self) -> Timespec {
+        self.ts
+    }
+
+    /// Metadata supplied with the credentials for this id.
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+
+    /// The scopes granted to the credentials for this id.
+    pub fn scopes(&self) -> &HashSet<String> {
+        &self.scopes
+    }
+
+    /// Check whether the credentials for this id were granted the given scope.  This is the
+    /// runtime equivalent of the `HawkScoped` guard.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.contains(scope)
+    }
+
+    /// The label of the key that verified the request's MAC, if the credential has one (see
+    /// `HawkCredential::key_id`).
+    pub fn key_id(&self) -> Option<&str> {
+        self.key_id.as_ref().map(|k| &k[..])
+    }
+
+    /// The validated header, for access to attributes such as `ext`, `app`, and `dlg`.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The payload hash from the request's header, if any.
+    pub fn payload_hash(&self) -> Option<&[u8]> {
+        self.header.hash.as_deref()
+    }
+
+    /// Begin checking the request body against the header's payload hash, for routes that
+    /// read the request's `Data` themselves rather than using `HawkVerifiedBody`.  The
+    /// `content_type` is the request's Content-Type header value.  This is `None` if the header
+    /// has no payload hash.
+    pub fn payload_verifier(&self, content_type: &str) -> Option<PayloadVerifier> {
+        let hash = self.header.hash.clone()?;
+        Some(PayloadVerifier::new(content_type, self.algorithm, hash))
+    }
+}
+
+impl Deref for HawkPrincipal {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.id
+    }
+}
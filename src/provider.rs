@@ -0,0 +1,498 @@
+use super::{HawkAlgorithm, HawkCredential, HawkRequestInfo};
+use hawk::Key;
+use rocket::config::{Config, Table, Value};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+
+/// A source of Hawk credentials, looked up by id.  Implement this trait to fetch keys from a
+/// database or other store, and install the provider with `HawkCredentials::provider`.
+///
+/// Lookups return a `HawkCredential` rather than a bare key, so that providers can supply the
+/// credential's algorithm, metadata, and scopes as well.  Credentials are cheap to clone.
+///
+/// Providers backed by another service return a `ProviderError` when it cannot be reached,
+/// rather than treating the id as unknown.  Such requests fail with
+/// `HawkError::ProviderUnavailable`, and the failure is not cached by `CachedProvider`.
+pub trait CredentialsProvider: Send + Sync + 'static {
+    /// Look up the credential for the given id, returning None if the id is unknown, or an
+    /// error if the provider could not tell.
+    fn lookup(&self, id: &str) -> Result<Option<HawkCredential>, ProviderError>;
+
+    /// Look up all active credentials for the given id, such as the old and new keys while a
+    /// secret is being rotated.  Requests are accepted if their MAC matches any of them, tried
+    /// in order; give each a `key_id` to see which matched.
+    ///
+    /// The default returns the result of `lookup`.
+    fn lookup_all(&self, id: &str) -> Result<Vec<HawkCredential>, ProviderError> {
+        Ok(self.lookup(id)?.into_iter().collect())
+    }
+
+    /// Look up all active credentials for the given id in the context of the given request,
+    /// such as to select credentials by tenant.  This is the lookup used when validating
+    /// requests.
+    ///
+    /// The default returns the result of `lookup_all`.
+    fn lookup_for(
+        &self,
+        id: &str,
+        _request: &HawkRequestInfo,
+    ) -> Result<Vec<HawkCredential>, ProviderError> {
+        self.lookup_all(id)
+    }
+}
+
+/// A failure of a `CredentialsProvider` to look up an id, with a description of the problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderError(pub String);
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// An in-memory credentials provider, mapping ids to credentials.
+#[derive(Clone, Default)]
+pub struct MapProvider {
+    credentials: HashMap<String, Vec<HawkCredential>>,
+}
+
+impl MapProvider {
+    /// Create a new, empty provider.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a credential with the given id and key, replacing any existing credential for that
+    /// id.
+    pub fn add<S: Into<String>>(self, id: S, key: Key) -> Self {
+        self.add_credential(id, HawkCredential::from_key(key))
+    }
+
+    /// Add a credential with the given id, replacing any existing credential for that id.
+    pub fn add_credential<S: Into<String>>(mut self, id: S, credential: HawkCredential) -> Self {
+        self.insert(id, credential);
+        self
+    }
+
+    /// Add a credential with the given id, in addition to any existing credentials for that id,
+    /// as when rotating its secret.
+    pub fn add_rotated<S: Into<String>>(mut self, id: S, credential: HawkCredential) -> Self {
+        self.credentials
+            .entry(id.into())
+            .or_default()
+            .push(credential);
+        self
+    }
+
+    /// Insert a credential with the given id, replacing any existing credentials for that id.
+    pub fn insert<S: Into<String>>(&mut self, id: S, credential: HawkCredential) {
+        self.credentials.insert(id.into(), vec![credential]);
+    }
+
+    /// Remove the credentials for the given id, returning them.
+    pub fn remove(&mut self, id: &str) -> Vec<HawkCredential> {
+        self.credentials.remove(id).unwrap_or_default()
+    }
+
+    /// Load credentials from the table with the given name in a Rocket configuration's extras.
+    /// Each entry in the table maps an id to a table with a base64-encoded `key`, an optional
+    /// `algorithm` (default `sha256`), and optional `scopes`:
+    ///
+    /// ```toml
+    /// [global.hawk_credentials.my-service]
+    /// key = "dGhpcyBpcyBhIHNlY3JldA=="
+    /// algorithm = "sha256"
+    /// scopes = ["payments:read"]
+    /// ```
+    ///
+    /// Any missing or invalid value results in an error describing the problem.
+    pub fn from_rocket_config(config: &Config, name: &str) -> Result<Self, String> {
+        let table = config
+            .get_table(name)
+            .map_err(|e| format!("{}: {}", name, e))?;
+        MapProvider::from_table(table).map_err(|e| format!("{}.{}", name, e))
+    }
+
+    /// Load credentials from a table in the format described for `from_rocket_config`.
+    pub(crate) fn from_table(table: &Table) -> Result<Self, String> {
+        let mut provider = MapProvider::new();
+        for (id, value) in table {
+            let credential = credential_from_value(value).map_err(|e| format!("{}: {}", id, e))?;
+            provider.insert(id.clone(), credential);
+        }
+        Ok(provider)
+    }
+
+    /// Load credentials from the environment variable with the given name, as a
+    /// comma-separated list of `id:algorithm:key` entries, where the key is base64-encoded,
+    /// such as `HAWK_KEYS="svc1:sha256:c2VjcmV0,svc2:sha512:b3RoZXI="`.  The algorithm may be
+    /// omitted (`id:key`), defaulting to `sha256`.
+    ///
+    /// A missing variable or an invalid entry results in an error describing the problem.
+    pub fn from_env(name: &str) -> Result<Self, String> {
+        let value = env::var(name).map_err(|e| format!("{}: {}", name, e))?;
+        MapProvider::parse_keys(&value).map_err(|e| format!("{}: {}", name, e))
+    }
+
+    /// Parse credentials in the format described for `from_env`.
+    pub fn parse_keys(keys: &str) -> Result<Self, String> {
+        let mut provider = MapProvider::new();
+        // errors identify entries by position, as the entries themselves contain secrets
+        let entries = keys.split(',').map(str::trim).filter(|e| !e.is_empty());
+        for (i, entry) in entries.enumerate() {
+            let parts: Vec<&str> = entry.split(':').collect();
+            let (id, algorithm, key) = match parts[..] {
+                [id, key] => (id, HawkAlgorithm::Sha256, key),
+                [id, algorithm, key] => {
+                    let algorithm = algorithm
+                        .parse()
+                        .map_err(|e| format!("entry {}: {}", i + 1, e))?;
+                    (id, algorithm, key)
+                }
+                _ => return Err(format!("entry {}: expected id:algorithm:key", i + 1)),
+            };
+            if id.is_empty() {
+                return Err(format!("entry {}: missing id", i + 1));
+            }
+            let secret =
+                base64::decode(key).map_err(|_| format!("key for {}: invalid base64", id))?;
+            if secret.is_empty() {
+                return Err(format!("key for {}: must not be empty", id));
+            }
+            provider.insert(id, HawkCredential::new(secret, algorithm));
+        }
+        Ok(provider)
+    }
+
+    /// The number of ids in the provider.
+    pub fn len(&self) -> usize {
+        self.credentials.len()
+    }
+
+    /// Check whether the provider has no credentials.
+    pub fn is_empty(&self) -> bool {
+        self.credentials.is_empty()
+    }
+}
+
+impl CredentialsProvider for MapProvider {
+    fn lookup(&self, id: &str) -> Result<Option<HawkCredential>, ProviderError> {
+        Ok(self.credentials.get(id).and_then(|c| c.first().cloned()))
+    }
+
+    fn lookup_all(&self, id: &str) -> Result<Vec<HawkCredential>, ProviderError> {
+        Ok(self.credentials.get(id).cloned().unwrap_or_default())
+    }
+}
+
+/// A credentials provider that consults a sequence of providers in order, using the first that
+/// knows the id, such as environment overrides, then a database, then static defaults.  If a
+/// provider fails, the lookup fails, rather than falling through to those after it.
+#[derive(Default)]
+pub struct ChainProvider(pub Vec<Box<dyn CredentialsProvider>>);
+
+impl ChainProvider {
+    /// Create a new, empty chain.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a provider to the end of the chain, to be consulted after those already added.
+    pub fn then<P: CredentialsProvider>(mut self, provider: P) -> Self {
+        self.0.push(Box::new(provider));
+        self
+    }
+}
+
+impl CredentialsProvider for ChainProvider {
+    fn lookup(&self, id: &str) -> Result<Option<HawkCredential>, ProviderError> {
+        for provider in &self.0 {
+            if let Some(credential) = provider.lookup(id)? {
+                return Ok(Some(credential));
+            }
+        }
+        Ok(None)
+    }
+
+    fn lookup_all(&self, id: &str) -> Result<Vec<HawkCredential>, ProviderError> {
+        for provider in &self.0 {
+            let credentials = provider.lookup_all(id)?;
+            if !credentials.is_empty() {
+                return Ok(credentials);
+            }
+        }
+        Ok(vec![])
+    }
+
+    fn lookup_for(
+        &self,
+        id: &str,
+        request: &HawkRequestInfo,
+    ) -> Result<Vec<HawkCredential>, ProviderError> {
+        for provider in &self.0 {
+            let credentials = provider.lookup_for(id, request)?;
+            if !credentials.is_empty() {
+                return Ok(credentials);
+            }
+        }
+        Ok(vec![])
+    }
+}
+
+/// Closures from ids to keys are credentials providers, so that prototypes and tests can
+/// supply a lookup inline, as in `HawkCredentials::new().provider(|id: &str| ..)`.  The
+/// argument's type must be given for the closure to accept any lifetime.  Credentials are
+/// created with `HawkCredential::from_key`.
+impl<F> CredentialsProvider for F
+where
+    F: Fn(&str) -> Option<Key> + Send + Sync + 'static,
+{
+    fn lookup(&self, id: &str) -> Result<Option<HawkCredential>, ProviderError> {
+        Ok(self(id).map(HawkCredential::from_key))
+    }
+}
+
+// Parse a single credential from a configuration table.
+pub(crate) fn credential_from_value(value: &Value) -> Result<HawkCredential, String> {
+    let table = value.as_table().ok_or("expected a table")?;
+    let mut key = None;
+    let mut algorithm = HawkAlgorithm::Sha256;
+    let mut scopes = vec![];
+    for (name, value) in table {
+        match &name[..] {
+            "key" => {
+                let encoded = value.as_str().ok_or("key: expected a string")?;
+                let secret = base64::decode(encoded).map_err(|_| "key: invalid base64")?;
+                if secret.is_empty() {
+                    return Err("key: must not be empty".to_string());
+                }
+                key = Some(secret);
+            }
+            "algorithm" => {
+                algorithm = value
+                    .as_str()
+                    .ok_or("algorithm: expected a string")?
+                    .parse()
+                    .map_err(|e| format!("algorithm: {}", e))?;
+            }
+            "scopes" => {
+                for scope in value.as_array().ok_or("scopes: expected an array")? {
+                    scopes.push(scope.as_str().ok_or("scopes: expected strings")?);
+                }
+            }
+            _ => return Err(format!("unknown setting {}", name)),
+        }
+    }
+
+    let key = key.ok_or("missing key")?;
+    Ok(scopes
+        .into_iter()
+        .fold(HawkCredential::new(key, algorithm), |c, s| c.scope(s)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ChainProvider, CredentialsProvider, MapProvider};
+    use crate::{HawkAlgorithm, HawkCredential};
+    use rocket::config::{Config, Environment, Table, Value};
+
+    // build a config with the given hawk_credentials entries
+    fn config(entries: Vec<(&str, Vec<(&str, Value)>)>) -> Config {
+        let mut credentials = Table::new();
+        for (id, settings) in entries {
+            let settings: Table = settings
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect();
+            credentials.insert(id.to_string(), Value::Table(settings));
+        }
+        Config::build(Environment::Development)
+            .extra("hawk_credentials", credentials)
+            .finalize()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_from_rocket_config() {
+        let config = config(vec![
+            ("a", vec![("key", Value::from(base64::encode([1u8; 32])))]),
+            (
+                "b",
+                vec![
+                    ("key", Value::from(base64::encode([2u8; 48]))),
+                    ("algorithm", Value::from("SHA-384")),
+                    ("scopes", Value::from(vec!["read"])),
+                ],
+            ),
+        ]);
+        let provider = MapProvider::from_rocket_config(&config, "hawk_credentials").unwrap();
+        assert_eq!(provider.len(), 2);
+        let b = provider.lookup("b").unwrap().unwrap();
+        assert_eq!(b.algorithm, Some(HawkAlgorithm::Sha384));
+        assert!(b.scopes.contains("read"));
+        assert_eq!(
+            provider.lookup("a").unwrap().and_then(|c| c.algorithm),
+            Some(HawkAlgorithm::Sha256)
+        );
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let provider = MapProvider::parse_keys("a:AQEB, b:sha384:AgIC,").unwrap();
+        assert_eq!(provider.len(), 2);
+        assert_eq!(
+            provider.lookup("a").unwrap().and_then(|c| c.algorithm),
+            Some(HawkAlgorithm::Sha256)
+        );
+        assert_eq!(
+            provider.lookup("b").unwrap().and_then(|c| c.algorithm),
+            Some(HawkAlgorithm::Sha384)
+        );
+        assert!(MapProvider::parse_keys("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_keys_invalid() {
+        let check = |keys: &str, expected: &str| {
+            assert_eq!(
+                MapProvider::parse_keys(keys).err(),
+                Some(expected.to_string())
+            );
+        };
+        check("a", "entry 1: expected id:algorithm:key");
+        check("a:AQEB,a:b:c:d", "entry 2: expected id:algorithm:key");
+        check(":AQEB", "entry 1: missing id");
+        check("a:md5:AQEB", "entry 1: Unknown Hawk algorithm md5");
+        check("a:!!", "key for a: invalid base64");
+        check("a:", "key for a: must not be empty");
+    }
+
+    #[test]
+    fn test_from_env() {
+        std::env::set_var("ROCKET_HAWK_TEST_KEYS", "a:AQEB");
+        let provider = MapProvider::from_env("ROCKET_HAWK_TEST_KEYS").unwrap();
+        assert!(provider.lookup("a").unwrap().is_some());
+        assert!(MapProvider::from_env("ROCKET_HAWK_TEST_MISSING").is_err());
+    }
+
+    #[test]
+    fn test_from_rocket_config_invalid() {
+        let check = |settings: Vec<(&str, Value)>, expected: &str| {
+            let config = config(vec![("a", settings)]);
+            assert_eq!(
+                MapProvider::from_rocket_config(&config, "hawk_credentials").err(),
+                Some(expected.to_string())
+            );
+        };
+        check(vec![], "hawk_credentials.a: missing key");
+        check(
+            vec![("key", Value::from("!!"))],
+            "hawk_credentials.a: key: invalid base64",
+        );
+        check(
+            vec![
+                ("key", Value::from("AA==")),
+                ("algorithm", Value::from("md5")),
+            ],
+            "hawk_credentials.a: algorithm: Unknown Hawk algorithm md5",
+        );
+        check(
+            vec![("key", Value::from("AA==")), ("kye", Value::from("AA=="))],
+            "hawk_credentials.a: unknown setting kye",
+        );
+        assert!(MapProvider::from_rocket_config(&config(vec![]), "missing").is_err());
+    }
+
+    #[test]
+    fn test_map_provider() {
+        let mut provider = MapProvider::new()
+            .add_credential(
+                "a",
+                HawkCredential::new(vec![1u8; 32], HawkAlgorithm::Sha256),
+            )
+            .add_credential(
+                "b",
+                HawkCredential::new(vec![2u8; 48], HawkAlgorithm::Sha384),
+            );
+        assert_eq!(provider.len(), 2);
+        assert_eq!(
+            provider.lookup("b").unwrap().and_then(|c| c.algorithm),
+            Some(HawkAlgorithm::Sha384)
+        );
+        assert!(provider.lookup("c").unwrap().is_none());
+
+        assert_eq!(provider.remove("a").len(), 1);
+        assert!(provider.lookup("a").unwrap().is_none());
+        assert!(!provider.is_empty());
+    }
+
+    #[test]
+    fn test_chain_provider() {
+        let overrides = MapProvider::new().add_credential(
+            "a",
+            HawkCredential::new(vec![1u8; 32], HawkAlgorithm::Sha512),
+        );
+        let defaults = MapProvider::new()
+            .add_credential(
+                "a",
+                HawkCredential::new(vec![2u8; 32], HawkAlgorithm::Sha256),
+            )
+            .add_credential(
+                "b",
+                HawkCredential::new(vec![3u8; 32], HawkAlgorithm::Sha256),
+            );
+        let chain = ChainProvider::new().then(overrides).then(defaults);
+        assert_eq!(
+            chain.lookup("a").unwrap().and_then(|c| c.algorithm),
+            Some(HawkAlgorithm::Sha512)
+        );
+        assert_eq!(chain.lookup_all("a").unwrap().len(), 1);
+        assert!(chain.lookup("b").unwrap().is_some());
+        assert!(chain.lookup("c").unwrap().is_none());
+        assert!(chain.lookup_all("c").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_closure() {
+        fn boxed<P: CredentialsProvider>(provider: P) -> Box<dyn CredentialsProvider> {
+            Box::new(provider)
+        }
+
+        let provider = boxed(|id: &str| {
+            if id == "me" {
+                Some(HawkAlgorithm::Sha256.key(vec![1u8; 32]))
+            } else {
+                None
+            }
+        });
+        assert!(provider.lookup("me").unwrap().is_some());
+        assert!(provider.lookup("you").unwrap().is_none());
+        assert_eq!(provider.lookup_all("me").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_map_provider_rotated() {
+        let old = HawkCredential::new(vec![1u8; 32], HawkAlgorithm::Sha256).key_id("old");
+        let new = HawkCredential::new(vec![2u8; 32], HawkAlgorithm::Sha256).key_id("new");
+        let provider = MapProvider::new()
+            .add_rotated("a", old)
+            .add_rotated("a", new);
+        let key_ids: Vec<_> = provider
+            .lookup_all("a")
+            .unwrap()
+            .into_iter()
+            .map(|c| c.key_id)
+            .collect();
+        assert_eq!(
+            key_ids,
+            vec![Some("old".to_string()), Some("new".to_string())]
+        );
+        assert_eq!(
+            provider.lookup("a").unwrap().and_then(|c| c.key_id),
+            Some("old".to_string())
+        );
+        assert!(provider.lookup_all("b").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,114 @@
+use super::{NonceStore, NonceStoreError};
+use r2d2::Pool;
+use std::fmt;
+use std::time::Duration;
+
+/// A nonce store in Redis, so that replay protection covers several instances of an application
+/// behind a load balancer.  This is available with the `redis` feature.
+///
+/// Each nonce is recorded with a single `SET <key> 1 NX EX <seconds>`, expiring when it no
+/// longer needs to be retained, using a pool of connections.
+/// If Redis cannot be reached, requests fail with `HawkError::NonceStoreUnavailable`, since their
+/// nonces cannot be checked.
+pub struct RedisNonceStore {
+    pool: Pool<redis::Client>,
+    prefix: String,
+}
+
+impl RedisNonceStore {
+    /// Create a store with connections from the given pool.
+    pub fn new(pool: Pool<redis::Client>) -> Self {
+        RedisNonceStore {
+            pool,
+            prefix: "hawk-nonce:".to_string(),
+        }
+    }
+
+    /// Create a store as for `new`, with a new pool of connections to the Redis server at the
+    /// given URL, such as `redis://127.0.0.1/`.
+    pub fn open(url: &str) -> Result<Self, String> {
+        let client = redis::Client::open(url).map_err(|e| e.to_string())?;
+        let pool = Pool::builder().build(client).map_err(|e| e.to_string())?;
+        Ok(RedisNonceStore::new(pool))
+    }
+
+    /// Set the prefix of the Redis keys, such as to share a server between applications.
+    ///
+    /// The default is `hawk-nonce:`.
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Record several keys in one round trip, using a pipeline, returning whether each was new
+    /// as for `NonceStore::check_and_store`.
+    pub fn check_and_store_all(
+        &self,
+        keys: &[&str],
+        retain: Duration,
+    ) -> Result<Vec<bool>, NonceStoreError> {
+        let mut pipe = redis::pipe();
+        for key in keys {
+            pipe.add_command(self.command(key, retain));
+        }
+        let result: Result<Vec<Option<String>>, String> = match self.pool.get() {
+            Ok(mut conn) => pipe.query(&mut *conn).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match result {
+            Ok(replies) => Ok(replies.into_iter().map(|r| r.is_some()).collect()),
+            Err(e) => Err(NonceStoreError(format!(
+                "could not record nonces in Redis: {}",
+                e
+            ))),
+        }
+    }
+
+    // Build the command recording a nonce, which replies OK if the nonce is new, and nil
+    // otherwise.
+    fn command(&self, key: &str, retain: Duration) -> redis::Cmd {
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(format!("{}{}", self.prefix, key))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(retain.as_secs().max(1));
+        cmd
+    }
+}
+
+impl NonceStore for RedisNonceStore {
+    fn check_and_store(&self, key: &str, retain: Duration) -> Result<bool, NonceStoreError> {
+        Ok(self.check_and_store_all(&[key], retain)?[0])
+    }
+}
+
+impl fmt::Debug for RedisNonceStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RedisNonceStore")
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RedisNonceStore;
+    use r2d2::Pool;
+    use std::time::Duration;
+
+    #[test]
+    fn test_command() {
+        // the pool is not used, and builds without a server when its minimum size is zero
+        let client = redis::Client::open("redis://127.0.0.1:1/").unwrap();
+        let pool = Pool::builder().min_idle(Some(0)).build_unchecked(client);
+        let store = RedisNonceStore::new(pool).prefix("p:");
+        let command = store.command("2:me:1000:abc", Duration::from_secs(120));
+        let packed = String::from_utf8(command.get_packed_command()).unwrap();
+        assert_eq!(
+            packed,
+            "*6\r\n$3\r\nSET\r\n$15\r\np:2:me:1000:abc\r\n$1\r\n1\r\n\
+             $2\r\nNX\r\n$2\r\nEX\r\n$3\r\n120\r\n"
+        );
+    }
+}
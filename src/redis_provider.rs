@@ -0,0 +1,215 @@
+use super::{CredentialsProvider, HawkAlgorithm, HawkCredential, ProviderError};
+use r2d2::Pool;
+use std::time::Duration;
+
+/// A credentials provider that reads keys from a Redis hash, with `HGET <hash> <id>`, using a
+/// pool of connections.  This is available with the `redis` feature.
+///
+/// Each value in the hash is a base64-encoded key, optionally preceded by an algorithm and a
+/// colon, such as `sha256:c2VjcmV0`; the default algorithm is `sha256`.  Invalid values are
+/// logged, and the id is treated as unknown.  Redis errors are returned as a `ProviderError`, so
+/// that the request fails with `HawkError::ProviderUnavailable`.
+///
+/// Fields of a Redis hash cannot expire individually, so entries with a time-to-live (see `ttl`
+/// and `insert`) are instead kept in their own keys, `<hash>:<id>`, with the same format.  Both
+/// places are read in one round trip.
+///
+/// Every lookup goes to Redis, so that changes made by a provisioning system are seen
+/// immediately.  To limit the load on Redis, wrap the provider in a `CachedProvider`, with which
+/// each entry expires individually after its time-to-live.
+pub struct RedisProvider {
+    pool: Pool<redis::Client>,
+    hash: String,
+    ttl: Option<Duration>,
+}
+
+impl RedisProvider {
+    /// Create a provider reading the given hash, with connections from the given pool.
+    pub fn new<S: Into<String>>(pool: Pool<redis::Client>, hash: S) -> Self {
+        RedisProvider {
+            pool,
+            hash: hash.into(),
+            ttl: None,
+        }
+    }
+
+    /// Create a provider reading the given hash, with a new pool of connections to the Redis
+    /// server at the given URL, such as `redis://127.0.0.1/`.
+    pub fn open<S: Into<String>>(url: &str, hash: S) -> Result<Self, String> {
+        let client = redis::Client::open(url).map_err(|e| e.to_string())?;
+        let pool = Pool::builder().build(client).map_err(|e| e.to_string())?;
+        Ok(RedisProvider::new(pool, hash))
+    }
+
+    /// Set the time-to-live of entries added with `insert`, after which Redis removes them and
+    /// their ids are unknown.
+    ///
+    /// The default is for entries to never expire.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Add or replace the key for the given id, as a provisioning system would, expiring after
+    /// the `ttl` if one is set.
+    pub fn insert(&self, id: &str, algorithm: HawkAlgorithm, secret: &[u8]) -> Result<(), String> {
+        let value = format!("{}:{}", algorithm, base64::encode(secret));
+        let mut conn = self.pool.get().map_err(|e| e.to_string())?;
+        self.insert_pipeline(id, &value)
+            .query(&mut *conn)
+            .map_err(|e| e.to_string())
+    }
+
+    // Build the commands reading the value for an id, from the hash and from its own key.
+    fn lookup_pipeline(&self, id: &str) -> redis::Pipeline {
+        let mut pipe = redis::pipe();
+        pipe.cmd("HGET")
+            .arg(&self.hash)
+            .arg(id)
+            .cmd("GET")
+            .arg(self.entry_key(id));
+        pipe
+    }
+
+    // Build the commands atomically writing the value for an id, with `SET .. EX` in its own key
+    // if it is to expire, and in the hash otherwise, removing it from the other place.
+    fn insert_pipeline(&self, id: &str, value: &str) -> redis::Pipeline {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        match self.ttl {
+            Some(ttl) => pipe
+                .cmd("HDEL")
+                .arg(&self.hash)
+                .arg(id)
+                .ignore()
+                .cmd("SET")
+                .arg(self.entry_key(id))
+                .arg(value)
+                .arg("EX")
+                .arg(ttl.as_secs().max(1))
+                .ignore(),
+            None => pipe
+                .cmd("DEL")
+                .arg(self.entry_key(id))
+                .ignore()
+                .cmd("HSET")
+                .arg(&self.hash)
+                .arg(id)
+                .arg(value)
+                .ignore(),
+        };
+        pipe
+    }
+
+    // The Redis key of an entry with a time-to-live.
+    fn entry_key(&self, id: &str) -> String {
+        format!("{}:{}", self.hash, id)
+    }
+}
+
+impl CredentialsProvider for RedisProvider {
+    fn lookup(&self, id: &str) -> Result<Option<HawkCredential>, ProviderError> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| ProviderError(format!("could not connect to Redis: {}", e)))?;
+        let (hashed, expiring): (Option<String>, Option<String>) = self
+            .lookup_pipeline(id)
+            .query(&mut *conn)
+            .map_err(|e| ProviderError(format!("Redis query failed: {}", e)))?;
+        let value = match Option::or(hashed, expiring) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        match parse_value(&value) {
+            Ok(credential) => Ok(Some(credential)),
+            Err(e) => {
+                rocket::logger::error(&format!("Invalid Hawk credentials for {}: {}", id, e));
+                Ok(None)
+            }
+        }
+    }
+}
+
+// Parse a credential from a hash value of the form `[algorithm:]key`.
+fn parse_value(value: &str) -> Result<HawkCredential, String> {
+    let (algorithm, key) = match value.find(':') {
+        Some(i) => (value[..i].parse()?, &value[i + 1..]),
+        None => (HawkAlgorithm::Sha256, value),
+    };
+    let secret = base64::decode(key.trim()).map_err(|_| "key: invalid base64")?;
+    if secret.is_empty() {
+        return Err("key: must not be empty".to_string());
+    }
+    Ok(HawkCredential::new(secret, algorithm))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_value, RedisProvider};
+    use crate::HawkAlgorithm;
+    use r2d2::Pool;
+    use std::time::Duration;
+
+    // a provider whose pool is not used, and builds without a server when its minimum size is
+    // zero
+    fn provider() -> RedisProvider {
+        let client = redis::Client::open("redis://127.0.0.1:1/").unwrap();
+        let pool = Pool::builder().min_idle(Some(0)).build_unchecked(client);
+        RedisProvider::new(pool, "creds")
+    }
+
+    #[test]
+    fn test_insert_ttl() {
+        let pipe = provider()
+            .ttl(Duration::from_secs(300))
+            .insert_pipeline("me", "sha256:AQEB");
+        let packed = String::from_utf8(pipe.get_packed_pipeline()).unwrap();
+        assert!(packed.contains("$4\r\nHDEL\r\n$5\r\ncreds\r\n$2\r\nme\r\n"));
+        assert!(packed.contains(
+            "*5\r\n$3\r\nSET\r\n$8\r\ncreds:me\r\n$11\r\nsha256:AQEB\r\n\
+             $2\r\nEX\r\n$3\r\n300\r\n"
+        ));
+
+        // without a ttl, entries are written to the hash
+        let pipe = provider().insert_pipeline("me", "sha256:AQEB");
+        let packed = String::from_utf8(pipe.get_packed_pipeline()).unwrap();
+        assert!(packed.contains("$4\r\nHSET\r\n"));
+        assert!(!packed.contains("\r\nEX\r\n"));
+    }
+
+    #[test]
+    fn test_lookup_pipeline() {
+        let pipe = provider().lookup_pipeline("me");
+        let packed = String::from_utf8(pipe.get_packed_pipeline()).unwrap();
+        assert_eq!(
+            packed,
+            "*3\r\n$4\r\nHGET\r\n$5\r\ncreds\r\n$2\r\nme\r\n\
+             *2\r\n$3\r\nGET\r\n$8\r\ncreds:me\r\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_value() {
+        let credential = parse_value("AQEB").unwrap();
+        assert_eq!(credential.algorithm, Some(HawkAlgorithm::Sha256));
+        let credential = parse_value("sha512:AQEB").unwrap();
+        assert_eq!(credential.algorithm, Some(HawkAlgorithm::Sha512));
+    }
+
+    #[test]
+    fn test_parse_value_invalid() {
+        assert_eq!(
+            parse_value("md5:AQEB").err(),
+            Some("Unknown Hawk algorithm md5".to_string())
+        );
+        assert_eq!(
+            parse_value("sha256:").err(),
+            Some("key: must not be empty".to_string())
+        );
+        assert_eq!(
+            parse_value("sha256:!!").err(),
+            Some("key: invalid base64".to_string())
+        );
+    }
+}
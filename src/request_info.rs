@@ -0,0 +1,320 @@
+use super::config::get_config;
+use super::{HawkConfig, HawkError, TrailingSlash};
+use hawk::RequestBuilder;
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+
+/// The details of a Rocket request that are included in a Hawk MAC: method, host, port, and
+/// path (including any query), with the path normalized as set in the `HawkConfig`.
+///
+/// This is also a request guard, failing with `HawkError::NoHost` if the request has no usable
+/// Host header.  Use `request_builder` to get a `hawk::RequestBuilder` for the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HawkRequestInfo {
+    method: &'static str,
+    host: String,
+    port: u16,
+    path: String,
+    tenant: Option<String>,
+}
+
+impl HawkRequestInfo {
+    /// Extract the request information from a Rocket request, returning None if the request
+    /// has no usable Host header.  If the Host header has no port, the default port from the
+    /// `HawkConfig` is used.
+    pub(crate) fn from_rocket_request(request: &Request) -> Option<Self> {
+        let config = get_config(request);
+        let (host, port) = parse_host(request.headers().get_one("host")?)?;
+        let port = port.unwrap_or(config.default_port);
+        let uri = request.uri();
+        let tenant = match config.tenant_header {
+            Some(ref name) => request.headers().get_one(name).map(|t| t.to_string()),
+            None => Some(host.to_ascii_lowercase()),
+        };
+        Some(HawkRequestInfo {
+            method: request.method().as_str(),
+            host: host.to_string(),
+            port,
+            path: normalize_uri(config, uri.path(), uri.query()),
+            tenant,
+        })
+    }
+
+    /// The request method, such as `GET`.
+    pub fn method(&self) -> &str {
+        self.method
+    }
+
+    /// The request host, without any port.  IPv6 literals retain their brackets, as in the
+    /// Host header.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// The request port.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The request path, including any query, as used in MAC calculations.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The tenant making the request, used by `TenantProvider` to select a set of
+    /// credentials.  This is the lowercased host, or the value of the header set with
+    /// `HawkConfig::tenant_header`.
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    /// Get a `hawk::RequestBuilder` for this request, to which a hash, ext, etc. may be added
+    /// before validating a header.
+    pub fn request_builder(&self) -> RequestBuilder<'_> {
+        RequestBuilder::new(self.method, &self.host, self.port, &self.path)
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for HawkRequestInfo {
+    type Error = HawkError;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        match HawkRequestInfo::from_rocket_request(request) {
+            Some(info) => Outcome::Success(info),
+            None => Outcome::Failure((Status::BadRequest, HawkError::NoHost)),
+        }
+    }
+}
+
+// Split a Host header value into host and optional port, handling bracketed IPv6 literals.
+fn parse_host(value: &str) -> Option<(&str, Option<u16>)> {
+    let value = value.trim();
+    let (host, port) = if value.starts_with('[') {
+        let end = value.find(']')? + 1;
+        match &value[end..] {
+            "" => (&value[..end], None),
+            rest if rest.starts_with(':') => (&value[..end], Some(&rest[1..])),
+            _ => return None,
+        }
+    } else {
+        match value.find(':') {
+            Some(i) => (&value[..i], Some(&value[i + 1..])),
+            None => (value, None),
+        }
+    };
+
+    if host.is_empty() {
+        return None;
+    }
+    match port {
+        Some(port) => Some((host, Some(port.parse().ok()?))),
+        None => Some((host, None)),
+    }
+}
+
+// Build the URI used in MAC calculations from the request's path and query, per the
+// normalization options in the config.
+fn normalize_uri(config: &HawkConfig, path: &str, query: Option<&str>) -> String {
+    let mut uri = match config.trailing_slash {
+        TrailingSlash::Preserve => path.to_string(),
+        TrailingSlash::Strip if path.len() > 1 => path.trim_end_matches('/').to_string(),
+        TrailingSlash::Strip => path.to_string(),
+        TrailingSlash::Append if path.ends_with('/') => path.to_string(),
+        TrailingSlash::Append => format!("{}/", path),
+    };
+    if uri.is_empty() {
+        uri.push('/');
+    }
+    if let Some(query) = query {
+        if config.include_query {
+            uri.push('?');
+            uri.push_str(query);
+        }
+    }
+    if config.normalize_percent_encoding {
+        uri = normalize_percent_encoding(&uri);
+    }
+    uri
+}
+
+// Decode percent-escaped unreserved characters and uppercase the hex digits of other escapes.
+fn normalize_percent_encoding(uri: &str) -> String {
+    let mut result = String::with_capacity(uri.len());
+    let mut rest = uri;
+    while let Some(i) = rest.find('%') {
+        result.push_str(&rest[..i]);
+        let escape = rest
+            .get(i + 1..i + 3)
+            .filter(|e| e.bytes().all(|b| b.is_ascii_hexdigit()));
+        match escape {
+            Some(escape) => {
+                let byte = u8::from_str_radix(escape, 16).unwrap_or_default();
+                if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                    result.push(byte as char);
+                } else {
+                    result.push('%');
+                    result.push_str(&escape.to_ascii_uppercase());
+                }
+                rest = &rest[i + 3..];
+            }
+            None => {
+                result.push('%');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::{normalize_percent_encoding, normalize_uri, parse_host, HawkRequestInfo};
+    use crate::{HawkConfig, HawkError, TrailingSlash};
+    use rocket::http::{Header, Status};
+    use rocket::local::Client;
+    use rocket::response::status;
+    use rocket::Rocket;
+
+    #[get("/path")]
+    fn index(info: Result<HawkRequestInfo, HawkError>) -> status::Custom<String> {
+        match info {
+            Ok(info) => status::Custom(
+                Status::Ok,
+                format!(
+                    "{} {} {} {}",
+                    info.method(),
+                    info.host(),
+                    info.port(),
+                    info.path()
+                ),
+            ),
+            Err(e) => status::Custom(Status::BadRequest, format!("{:?}", e)),
+        }
+    }
+
+    fn check(rocket: Rocket, host: Option<&'static str>, expected: (Status, &str)) {
+        let client = Client::new(rocket).unwrap();
+        let mut req = client.get("/path?q=1");
+        if let Some(host) = host {
+            req = req.header(Header::new("Host", host));
+        }
+        let mut res = req.dispatch();
+        assert_eq!(
+            (res.status(), res.body_string()),
+            (expected.0, Some(expected.1.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_host() {
+        assert_eq!(parse_host("example.com"), Some(("example.com", None)));
+        assert_eq!(
+            parse_host("example.com:8000"),
+            Some(("example.com", Some(8000)))
+        );
+        assert_eq!(parse_host(" example.com "), Some(("example.com", None)));
+        assert_eq!(parse_host("[2001:db8::1]"), Some(("[2001:db8::1]", None)));
+        assert_eq!(
+            parse_host("[2001:db8::1]:8443"),
+            Some(("[2001:db8::1]", Some(8443)))
+        );
+    }
+
+    #[test]
+    fn test_parse_host_invalid() {
+        assert_eq!(parse_host(""), None);
+        assert_eq!(parse_host(":80"), None);
+        assert_eq!(parse_host("example.com:http"), None);
+        assert_eq!(parse_host("[2001:db8::1"), None);
+        assert_eq!(parse_host("[2001:db8::1]8443"), None);
+    }
+
+    #[test]
+    fn test_normalize_percent_encoding() {
+        assert_eq!(normalize_percent_encoding("/a%7eb%2fc"), "/a~b%2Fc");
+        assert_eq!(normalize_percent_encoding("/%41%2D%5f"), "/A-_");
+        assert_eq!(normalize_percent_encoding("/100%"), "/100%");
+        assert_eq!(normalize_percent_encoding("/%zz%4"), "/%zz%4");
+    }
+
+    #[test]
+    fn test_normalize_uri() {
+        let config = HawkConfig::default();
+        assert_eq!(normalize_uri(&config, "/a/", Some("q=1")), "/a/?q=1");
+
+        let config = HawkConfig::default().include_query(false);
+        assert_eq!(normalize_uri(&config, "/a", Some("q=1")), "/a");
+
+        let config = HawkConfig::default().trailing_slash(TrailingSlash::Strip);
+        assert_eq!(normalize_uri(&config, "/a/", Some("q=1")), "/a?q=1");
+        assert_eq!(normalize_uri(&config, "/", None), "/");
+
+        let config = HawkConfig::default().trailing_slash(TrailingSlash::Append);
+        assert_eq!(normalize_uri(&config, "/a", None), "/a/");
+        assert_eq!(normalize_uri(&config, "/a/", None), "/a/");
+
+        let config = HawkConfig::default().normalize_percent_encoding(true);
+        assert_eq!(normalize_uri(&config, "/%7ea", Some("x=%2f")), "/~a?x=%2F");
+    }
+
+    #[test]
+    fn test_guard() {
+        let rocket = rocket::ignite().mount("/", routes![index]);
+        check(
+            rocket,
+            Some("example.com:8000"),
+            (Status::Ok, "GET example.com 8000 /path?q=1"),
+        );
+    }
+
+    #[test]
+    fn test_guard_default_port() {
+        let rocket = rocket::ignite()
+            .manage(HawkConfig::default().default_port(443))
+            .mount("/", routes![index]);
+        check(
+            rocket,
+            Some("example.com"),
+            (Status::Ok, "GET example.com 443 /path?q=1"),
+        );
+    }
+
+    #[test]
+    fn test_guard_no_host() {
+        let rocket = rocket::ignite().mount("/", routes![index]);
+        check(rocket, None, (Status::BadRequest, "NoHost"));
+    }
+
+    #[test]
+    fn test_tenant() {
+        #[get("/tenant")]
+        fn tenant(info: HawkRequestInfo) -> String {
+            info.tenant().unwrap_or("none").to_string()
+        }
+
+        let client = Client::new(rocket::ignite().mount("/", routes![tenant])).unwrap();
+        let mut res = client
+            .get("/tenant")
+            .header(Header::new("Host", "Example.COM:8000"))
+            .dispatch();
+        assert_eq!(res.body_string(), Some("example.com".to_string()));
+
+        let rocket = rocket::ignite()
+            .manage(HawkConfig::default().tenant_header("X-Tenant"))
+            .mount("/", routes![tenant]);
+        let client = Client::new(rocket).unwrap();
+        let get = |tenant: Option<&'static str>| {
+            let mut req = client
+                .get("/tenant")
+                .header(Header::new("Host", "example.com"));
+            if let Some(tenant) = tenant {
+                req = req.header(Header::new("X-Tenant", tenant));
+            }
+            req.dispatch().body_string()
+        };
+        assert_eq!(get(Some("acme")), Some("acme".to_string()));
+        assert_eq!(get(None), Some("none".to_string()));
+    }
+}
@@ -0,0 +1,96 @@
+use super::HawkCredential;
+use std::collections::HashSet;
+use std::sync::RwLock;
+
+/// A set of revoked ids and key fingerprints, checked before MAC verification.  Requests from
+/// a revoked id, or whose only keys are revoked, fail with `HawkError::Revoked`.
+///
+/// This is part of the managed `HawkCredentials` (see `HawkCredentials::revocations`), and can
+/// be updated at runtime, such as from an administrative route, taking effect immediately even
+/// if the credentials themselves are cached.
+#[derive(Debug, Default)]
+pub struct RevocationList {
+    ids: RwLock<HashSet<String>>,
+    fingerprints: RwLock<HashSet<String>>,
+}
+
+impl RevocationList {
+    /// Revoke all credentials for the given id.
+    pub fn revoke_id<S: Into<String>>(&self, id: S) {
+        self.ids.write().unwrap().insert(id.into());
+    }
+
+    /// Revoke the key with the given fingerprint (see `HawkCredential::fingerprint`), leaving
+    /// any other keys for the same id usable.
+    pub fn revoke_key<S: Into<String>>(&self, fingerprint: S) {
+        self.fingerprints
+            .write()
+            .unwrap()
+            .insert(fingerprint.into());
+    }
+
+    /// Restore an id revoked with `revoke_id`.
+    pub fn restore_id(&self, id: &str) {
+        self.ids.write().unwrap().remove(id);
+    }
+
+    /// Restore a key revoked with `revoke_key`.
+    pub fn restore_key(&self, fingerprint: &str) {
+        self.fingerprints.write().unwrap().remove(fingerprint);
+    }
+
+    /// Replace the entire list, such as after fetching it from a central service.
+    pub fn replace<I, F>(&self, ids: I, fingerprints: F)
+    where
+        I: IntoIterator<Item = String>,
+        F: IntoIterator<Item = String>,
+    {
+        let ids = ids.into_iter().collect();
+        let fingerprints = fingerprints.into_iter().collect();
+        *self.ids.write().unwrap() = ids;
+        *self.fingerprints.write().unwrap() = fingerprints;
+    }
+
+    /// Check whether the given id is revoked.
+    pub fn is_id_revoked(&self, id: &str) -> bool {
+        self.ids.read().unwrap().contains(id)
+    }
+
+    /// Check whether the given credential's key is revoked.
+    pub fn is_key_revoked(&self, credential: &HawkCredential) -> bool {
+        match credential.fingerprint {
+            Some(ref fingerprint) => self.fingerprints.read().unwrap().contains(fingerprint),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RevocationList;
+    use crate::{HawkAlgorithm, HawkCredential};
+
+    #[test]
+    fn test_ids() {
+        let list = RevocationList::default();
+        list.revoke_id("a");
+        assert!(list.is_id_revoked("a"));
+        assert!(!list.is_id_revoked("b"));
+        list.restore_id("a");
+        assert!(!list.is_id_revoked("a"));
+    }
+
+    #[test]
+    fn test_keys() {
+        let list = RevocationList::default();
+        let one = HawkCredential::new(vec![1u8; 32], HawkAlgorithm::Sha256);
+        let two = HawkCredential::new(vec![2u8; 32], HawkAlgorithm::Sha256);
+        list.revoke_key(one.fingerprint().unwrap());
+        assert!(list.is_key_revoked(&one));
+        assert!(!list.is_key_revoked(&two));
+
+        list.replace(vec!["a".to_string()], vec![]);
+        assert!(!list.is_key_revoked(&one));
+        assert!(list.is_id_revoked("a"));
+    }
+}
@@ -0,0 +1,128 @@
+use super::{DefaultPolicy, HawkAuthenticated, HawkError, HawkPolicy, HawkPrincipal};
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+/// A scope required by the `HawkScoped` guard.  Scopes are zero-sized marker types naming the
+/// scope string, such as a `PaymentsWrite` type with `SCOPE` set to `"payments:write"`.
+pub trait HawkScope: Send + Sync + 'static {
+    /// The scope that the caller's credentials must have been granted.
+    const SCOPE: &'static str;
+}
+
+/// A request guard that authenticates the request as for `HawkAuthenticated<P>`, then requires
+/// that the caller's credentials were granted the scope `S` (see `HawkCredential::scope`).
+/// Authenticated requests lacking the scope fail with status 403 and
+/// `HawkError::MissingScope`.
+///
+/// To check scopes at runtime instead, use `HawkPrincipal::has_scope`.
+///
+/// This dereferences to the `HawkPrincipal` for the authenticated caller.
+pub struct HawkScoped<S: HawkScope, P: HawkPolicy = DefaultPolicy>(
+    HawkPrincipal,
+    PhantomData<(S, P)>,
+);
+
+impl<S: HawkScope, P: HawkPolicy> HawkScoped<S, P> {
+    /// Get the principal, consuming the guard.
+    pub fn into_inner(self) -> HawkPrincipal {
+        self.0
+    }
+}
+
+impl<'a, 'r, S: HawkScope, P: HawkPolicy> FromRequest<'a, 'r> for HawkScoped<S, P> {
+    type Error = HawkError;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let principal = request.guard::<HawkAuthenticated<P>>()?.into_inner();
+        if principal.has_scope(S::SCOPE) {
+            Outcome::Success(HawkScoped(principal, PhantomData))
+        } else {
+            Outcome::Failure((
+                Status::Forbidden,
+                HawkError::MissingScope(S::SCOPE.to_string()),
+            ))
+        }
+    }
+}
+
+impl<S: HawkScope, P: HawkPolicy> Deref for HawkScoped<S, P> {
+    type Target = HawkPrincipal;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S: HawkScope, P: HawkPolicy> fmt::Debug for HawkScoped<S, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("HawkScoped")
+            .field(&S::SCOPE)
+            .field(&self.0)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HawkScope, HawkScoped};
+    use crate::{HawkAlgorithm, HawkCredential, HawkCredentials};
+    use hawk::{Credentials, RequestBuilder};
+    use rocket::http::{Header, Status};
+    use rocket::local::Client;
+
+    struct PaymentsWrite;
+
+    impl HawkScope for PaymentsWrite {
+        const SCOPE: &'static str = "payments:write";
+    }
+
+    #[post("/pay")]
+    fn pay(hawk: HawkScoped<PaymentsWrite>) -> String {
+        hawk.id().to_string()
+    }
+
+    fn check(id: &str, expected: Status) {
+        let credentials = HawkCredentials::new()
+            .add_credential(
+                "payer",
+                HawkCredential::new(vec![1u8; 32], HawkAlgorithm::Sha256).scope("payments:write"),
+            )
+            .add_credential(
+                "reader",
+                HawkCredential::new(vec![2u8; 32], HawkAlgorithm::Sha256).scope("payments:read"),
+            );
+        let secret = if id == "payer" { 1u8 } else { 2u8 };
+        let header = RequestBuilder::new("POST", "localhost", 80, "/pay")
+            .request()
+            .make_header(&Credentials {
+                id: id.to_string(),
+                key: HawkAlgorithm::Sha256.key(vec![secret; 32]),
+            })
+            .unwrap();
+
+        let rocket = rocket::ignite()
+            .manage(credentials)
+            .mount("/", routes![pay]);
+        let client = Client::new(rocket).unwrap();
+        let res = client
+            .post("/pay")
+            .header(Header::new("Host", "localhost"))
+            .header(Header::new("Authorization", format!("Hawk {}", header)))
+            .dispatch();
+        assert_eq!(res.status(), expected);
+    }
+
+    #[test]
+    fn test_has_scope() {
+        check("payer", Status::Ok);
+    }
+
+    #[test]
+    fn test_missing_scope() {
+        check("reader", Status::Forbidden);
+    }
+}
@@ -0,0 +1,180 @@
+use super::nonce::hash_key;
+use super::{NonceStore, NonceStoreError};
+use std::convert::TryInto;
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A nonce store in a sled database on disk, so that replay protection survives restarts of the
+/// application.  This is available with the `sled` feature.
+///
+/// Each nonce is recorded with an atomic compare-and-swap, with its expiration time as the
+/// value; a nonce whose entry has expired may be recorded again.  Keys are hashed before they
+/// are stored, so the database reveals nothing about the requests.  Expired entries are removed
+/// by `purge` (see `HawkConfig::purge_fairing`), which scans the whole tree.  If the database
+/// cannot be read or written, requests fail with `HawkError::NonceStoreUnavailable`.
+pub struct SledNonceStore {
+    tree: sled::Tree,
+    sync: bool,
+}
+
+impl SledNonceStore {
+    /// Create a store in the given tree, such as one opened from an application's existing
+    /// database with `Db::open_tree`.
+    pub fn new(tree: sled::Tree) -> Self {
+        SledNonceStore { tree, sync: false }
+    }
+
+    /// Create a store as for `new`, in the default tree of the database at the given path,
+    /// creating it if necessary, and returning an error describing the problem if it cannot be
+    /// opened.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let db = sled::open(path).map_err(|e| e.to_string())?;
+        Ok(SledNonceStore::new((*db).clone()))
+    }
+
+    /// Flush each nonce to disk before accepting its request, so that nonces survive a crash of
+    /// the machine as well as of the application, at a cost in latency.
+    ///
+    /// The default is to leave flushing to sled, which flushes every half second.
+    pub fn sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    // Record the key with the given expiration time, if it is absent or has expired.
+    fn record(&self, key: &str, expires: u64, now: u64) -> sled::Result<bool> {
+        let new = expires.to_be_bytes();
+        match self
+            .tree
+            .compare_and_swap(key, None as Option<&[u8]>, Some(&new[..]))?
+        {
+            Ok(()) => Ok(true),
+            Err(e) => match e.current {
+                // a concurrent request that also found the entry expired may have replaced it
+                // first, in which case this swap fails
+                Some(current) if expiry(&current) <= now => Ok(self
+                    .tree
+                    .compare_and_swap(key, Some(current), Some(&new[..]))?
+                    .is_ok()),
+                _ => Ok(false),
+            },
+        }
+    }
+}
+
+impl NonceStore for SledNonceStore {
+    fn check_and_store(&self, key: &str, retain: Duration) -> Result<bool, NonceStoreError> {
+        let now = unix_now();
+        let expires = now + retain.as_secs().max(1);
+        let recorded = self.record(&hash_key(key), expires, now).and_then(|fresh| {
+            if fresh && self.sync {
+                self.tree.flush()?;
+            }
+            Ok(fresh)
+        });
+        recorded.map_err(|e| NonceStoreError(format!("could not record nonce in sled: {}", e)))
+    }
+
+    fn purge(&self) {
+        let now = unix_now();
+        let purged = self.tree.iter().try_for_each(|entry| -> sled::Result<()> {
+            let (key, value) = entry?;
+            if expiry(&value) <= now {
+                // an entry recorded again since it was read is left alone
+                self.tree
+                    .compare_and_swap(key, Some(value), None as Option<&[u8]>)?
+                    .ok();
+            }
+            Ok(())
+        });
+        if let Err(e) = purged {
+            rocket::logger::error(&format!("Could not purge Hawk nonces from sled: {}", e));
+        }
+    }
+}
+
+impl fmt::Debug for SledNonceStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SledNonceStore")
+            .field("name", &String::from_utf8_lossy(&self.tree.name()))
+            .field("sync", &self.sync)
+            .finish()
+    }
+}
+
+// Parse the expiration time stored for a nonce, treating a malformed value as expired.
+fn expiry(value: &[u8]) -> u64 {
+    value.try_into().map(u64::from_be_bytes).unwrap_or(0)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::SledNonceStore;
+    use crate::nonce::hash_key;
+    use crate::NonceStore;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    const RETAIN: Duration = Duration::from_secs(120);
+
+    // a path for a temporary database, unique to the test
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rocket-hawk-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_restart() {
+        let path = temp_path("nonces");
+        let _ = fs::remove_dir_all(&path);
+        {
+            let store = SledNonceStore::open(&path).unwrap();
+            assert_eq!(store.check_and_store("a", RETAIN), Ok(true));
+            assert_eq!(store.check_and_store("a", RETAIN), Ok(false));
+        }
+
+        // nonces are remembered by a new store for the same database
+        let store = SledNonceStore::open(&path).unwrap().sync(true);
+        assert_eq!(store.check_and_store("a", RETAIN), Ok(false));
+        assert_eq!(store.check_and_store("b", RETAIN), Ok(true));
+        drop(store);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_expired() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let store = SledNonceStore::new(db.open_tree("nonces").unwrap());
+        store
+            .tree
+            .insert(hash_key("a"), &1u64.to_be_bytes()[..])
+            .unwrap();
+
+        // an expired nonce may be recorded again
+        assert_eq!(store.check_and_store("a", RETAIN), Ok(true));
+        assert_eq!(store.check_and_store("a", RETAIN), Ok(false));
+    }
+
+    #[test]
+    fn test_purge() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let store = SledNonceStore::new(db.open_tree("nonces").unwrap());
+        store
+            .tree
+            .insert("expired", &1u64.to_be_bytes()[..])
+            .unwrap();
+        store.tree.insert("malformed", "x").unwrap();
+        store.check_and_store("a", RETAIN).unwrap();
+        store.purge();
+        let keys: Vec<_> = store.tree.iter().keys().map(|k| k.unwrap()).collect();
+        assert_eq!(keys, vec![hash_key("a").as_bytes()]);
+    }
+}
@@ -0,0 +1,49 @@
+use super::{CredentialsProvider, HawkCredential, HawkRequestInfo, ProviderError};
+use std::collections::HashMap;
+
+/// A credentials provider that keeps a separate provider for each tenant, so that one
+/// application can serve many tenants with isolated key spaces.  The tenant of each request is
+/// taken from its Host header, or from the header set with `HawkConfig::tenant_header`.
+///
+/// Requests from unknown tenants, or with no tenant, fail with `HawkError::UnknownId`.  Since
+/// lookups without a request have no tenant, `lookup` and `lookup_all` always find nothing, and
+/// a `CachedProvider` should wrap the per-tenant providers rather than this one.
+#[derive(Default)]
+pub struct TenantProvider {
+    tenants: HashMap<String, Box<dyn CredentialsProvider>>,
+}
+
+impl TenantProvider {
+    /// Create a new provider with no tenants.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a tenant, replacing any existing provider for it.  Tenants taken from the Host
+    /// header are lowercased, so host names should be given in lowercase.
+    pub fn tenant<S: Into<String>, P: CredentialsProvider>(
+        mut self,
+        tenant: S,
+        provider: P,
+    ) -> Self {
+        self.tenants.insert(tenant.into(), Box::new(provider));
+        self
+    }
+}
+
+impl CredentialsProvider for TenantProvider {
+    fn lookup(&self, _id: &str) -> Result<Option<HawkCredential>, ProviderError> {
+        Ok(None)
+    }
+
+    fn lookup_for(
+        &self,
+        id: &str,
+        request: &HawkRequestInfo,
+    ) -> Result<Vec<HawkCredential>, ProviderError> {
+        match request.tenant().and_then(|t| self.tenants.get(t)) {
+            Some(provider) => provider.lookup_for(id, request),
+            None => Ok(vec![]),
+        }
+    }
+}
@@ -0,0 +1,608 @@
+use super::config::get_config;
+use super::{
+    AuthorizationHeader, DefaultPolicy, HawkError, HawkField, HawkPolicy, HawkRequestInfo,
+};
+use hawk::Header;
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request, State};
+use rocket::Outcome;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::Mutex;
+use time::Duration;
+
+/// A HawkValidator checks a parsed Hawk header against the request it arrived with, producing
+/// a principal representing the authenticated caller.  Manage an instance of the validator
+/// with `rocket.manage(..)` and use the `Hawk<V>` request guard in routes.
+pub trait HawkValidator: Send + Sync + 'static {
+    /// The type representing a successfully authenticated caller.  Verification is performed
+    /// once per request, and each guard gets a clone of the resulting principal.
+    type Principal: Clone + Send + Sync + 'static;
+
+    /// Validate the header for the given request, returning the principal on success, or the
+    /// status and error with which the request guard should fail.
+    fn validate(
+        &self,
+        header: &Header,
+        request: &HawkRequestInfo,
+    ) -> Result<Self::Principal, (Status, HawkError)>;
+
+    /// The maximum allowed timestamp skew for requests from the given principal, overriding
+    /// the `HawkConfig`.  A `HawkPolicy` with a narrower skew still applies.  The default
+    /// returns None, for no override.
+    fn ts_skew(&self, _principal: &Self::Principal) -> Option<Duration> {
+        None
+    }
+
+    /// Whether to check the nonces of requests from the given principal against the
+    /// `HawkConfig::nonce_store`, unless a `HawkPolicy` decides otherwise.  The default returns
+    /// true.
+    fn check_nonce(&self, _principal: &Self::Principal) -> bool {
+        true
+    }
+}
+
+/// A request guard that parses the "Authorization" header and validates it with the validator
+/// `V` managed by the Rocket instance, applying the policy `P` (see `HawkPolicy`).  This
+/// dereferences to the resulting principal.
+///
+/// The header is validated only once per request, no matter how many guards or policies use it.
+pub struct Hawk<V: HawkValidator, P: HawkPolicy = DefaultPolicy>(V::Principal, PhantomData<P>);
+
+impl<V: HawkValidator, P: HawkPolicy> Hawk<V, P> {
+    /// Get the principal, consuming the guard.
+    pub fn into_inner(self) -> V::Principal {
+        self.0
+    }
+}
+
+impl<'a, 'r, V: HawkValidator, P: HawkPolicy> FromRequest<'a, 'r> for Hawk<V, P> {
+    type Error = HawkError;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let validator = match request.guard::<State<V>>() {
+            Outcome::Success(validator) => validator,
+            _ => return Outcome::Failure((Status::InternalServerError, HawkError::NoValidator)),
+        };
+        validate_request::<V, P>(request, validator.inner()).map(|p| Hawk(p, PhantomData))
+    }
+}
+
+impl<V: HawkValidator, P: HawkPolicy> Deref for Hawk<V, P> {
+    type Target = V::Principal;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<V: HawkValidator, P: HawkPolicy> fmt::Debug for Hawk<V, P>
+where
+    V::Principal: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Hawk").field(&self.0).finish()
+    }
+}
+
+/// Parse the Authorization header from the request and verify it under the policy `P`.
+///
+/// The validator's part of the verification is performed only once per request and validator,
+/// no matter how many guards or policies use it; the policy's checks are then applied to the
+/// cached principal.
+pub(crate) fn validate_request<V: HawkValidator, P: HawkPolicy>(
+    request: &Request,
+    validator: &V,
+) -> request::Outcome<V::Principal, HawkError> {
+    let header = request.guard::<AuthorizationHeader>()?;
+    let verify = || verify_header(request, &header, validator);
+    let mut failure = None;
+    let cached = request.local_cache(|| match verify() {
+        Ok(principal) => CachedPrincipal::<V>(Some(principal)),
+        Err(e) => {
+            failure = Some(e);
+            CachedPrincipal(None)
+        }
+    });
+    let principal = match (&cached.0, failure) {
+        (Some(principal), _) => principal.clone(),
+        (None, Some(failure)) => return Outcome::Failure(failure),
+        // verification failed in an earlier guard; errors are not cached, so verify again
+        (None, None) => match verify() {
+            Ok(principal) => principal,
+            Err(failure) => return Outcome::Failure(failure),
+        },
+    };
+    match check_policy::<V, P>(request, &header, validator, principal) {
+        Ok(principal) => Outcome::Success(principal),
+        Err(failure) => Outcome::Failure(failure),
+    }
+}
+
+// The principal from a successful verification with validator V, cached for the duration of a
+// request; None if verification failed.
+struct CachedPrincipal<V: HawkValidator>(Option<V::Principal>);
+
+// The outcome of each nonce check made for a request, by replay key, so that a nonce is checked
+// against the store at most once however many guards verify the request.
+struct NonceChecks(Mutex<HashMap<String, bool>>);
+
+/// Verify a parsed Hawk header for the given Rocket request: check that it has all fields
+/// required by the `HawkConfig` and that its `ts` and `nonce` are within the configured format
+/// limits, validate it with the given validator (such as `HawkCredentials`), and apply the
+/// checks of the `DefaultPolicy`: that its timestamp is within the skew for the request, and,
+/// when a `HawkConfig::nonce_store` is configured, the method's nonces are checked, and
+/// `HawkValidator::check_nonce` allows it, that its nonce has not been used before.  That check
+/// records the nonce in the store.
+///
+/// This is the same verification performed by the `Hawk<V>` and `HawkAuthenticated` request
+/// guards, for use where request guards are not available, such as in fairings.  On failure,
+/// it returns the status and error with which a guard would have failed.
+///
+/// The outcome of each nonce check is cached for the rest of the request, so a fairing that
+/// calls this before a guard verifies the same request does not cause the guard to see the
+/// nonce as replayed.  Verifying the header against another request, or with a fresh copy of
+/// the request, consumes the nonce again.
+pub fn verify_rocket_request<V: HawkValidator>(
+    request: &Request,
+    header: &Header,
+    validator: &V,
+) -> Result<V::Principal, (Status, HawkError)> {
+    verify_with_policy::<V, DefaultPolicy>(request, header, validator)
+}
+
+/// Like `verify_rocket_request`, but applying the policy `P` on top of the `HawkConfig`.
+pub(crate) fn verify_with_policy<V: HawkValidator, P: HawkPolicy>(
+    request: &Request,
+    header: &Header,
+    validator: &V,
+) -> Result<V::Principal, (Status, HawkError)> {
+    let principal = verify_header(request, header, validator)?;
+    check_policy::<V, P>(request, header, validator, principal)
+}
+
+// Check the header against the HawkConfig and validate it with the validator.  None of this
+// depends on the policy, so the result can be shared by guards with different policies.
+fn verify_header<V: HawkValidator>(
+    request: &Request,
+    header: &Header,
+    validator: &V,
+) -> Result<V::Principal, (Status, HawkError)> {
+    let config = get_config(request);
+    if let Err(e) = config.check_formats(header) {
+        return Err((Status::BadRequest, e));
+    }
+    if let Err(field) = config.check_required(request.method(), header) {
+        return Err((Status::BadRequest, HawkError::MissingField(field)));
+    }
+    if !config.check_payload_hash(request.method(), header) {
+        return Err((Status::BadRequest, HawkError::MissingPayloadHash));
+    }
+
+    let info = match HawkRequestInfo::from_rocket_request(request) {
+        Some(info) => info,
+        None => return Err((Status::BadRequest, HawkError::NoHost)),
+    };
+    validator.validate(header, &info)
+}
+
+// Apply the policy P to a header that the validator has already accepted: check the payload
+// hash requirement and the timestamp skew, then the nonce.
+fn check_policy<V: HawkValidator, P: HawkPolicy>(
+    request: &Request,
+    header: &Header,
+    validator: &V,
+    principal: V::Principal,
+) -> Result<V::Principal, (Status, HawkError)> {
+    let config = get_config(request);
+    if P::REQUIRE_HASH && header.hash.is_none() {
+        return Err((Status::BadRequest, HawkError::MissingField(HawkField::Hash)));
+    }
+
+    // the timestamp is checked only after the validator has authenticated the header, so that
+    // a StaleTimestamp failure indicates a client with a bad clock rather than a forgery
+    let allowed = P::ts_skew_for(config.ts_skew, validator.ts_skew(&principal));
+    if let Some(ts) = header.ts {
+        // compare whole seconds, as the client-supplied timestamp may be far enough from now
+        // that the difference would overflow a Duration
+        let skew = config.clock.now().sec.abs_diff(ts.sec);
+        if skew > allowed.num_seconds().max(0) as u64 {
+            return Err((Status::Unauthorized, HawkError::StaleTimestamp));
+        }
+    }
+
+    // nonces are recorded only for requests that are otherwise valid, and retained until the
+    // request would fail with StaleTimestamp, which may be twice the skew if it was stamped in
+    // the future
+    let check_nonce = P::CHECK_NONCE.unwrap_or_else(|| {
+        config.nonce_store.is_some()
+            && config.check_nonce_for(request.method())
+            && validator.check_nonce(&principal)
+    });
+    if check_nonce {
+        let store = match config.nonce_store {
+            Some(ref store) => store,
+            None => return Err((Status::InternalServerError, HawkError::NoNonceStore)),
+        };
+        let key = match config.replay_key.key(header) {
+            Some(key) => key,
+            None => return Err((Status::BadRequest, HawkError::BadNonceFormat)),
+        };
+        let checks = request.local_cache(|| NonceChecks(Mutex::new(HashMap::new())));
+        let mut checks = checks.0.lock().unwrap();
+        let fresh = match checks.get(&key) {
+            Some(&fresh) => fresh,
+            None => {
+                let retain = (allowed * 2).to_std().unwrap_or_default();
+                // failures of the store are not replays, and are not cached, so a later guard
+                // will try the store again
+                let fresh = match store.check_and_store(&key, retain) {
+                    Ok(fresh) => fresh,
+                    Err(e) => {
+                        rocket::logger::error(&format!("Could not check Hawk nonce: {}", e));
+                        let e = HawkError::NonceStoreUnavailable;
+                        return Err((Status::ServiceUnavailable, e));
+                    }
+                };
+                if !fresh {
+                    config.report_replay(request, header);
+                }
+                checks.insert(key, fresh);
+                fresh
+            }
+        };
+        if !fresh {
+            return Err((Status::Unauthorized, HawkError::ReplayedNonce));
+        }
+    }
+
+    Ok(principal)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{verify_rocket_request, Hawk, HawkValidator};
+    use crate::AuthorizationHeader;
+    use crate::RecordingNonceStore;
+    use crate::{FixedClock, HawkConfig, HawkError, HawkField, HawkRequestInfo};
+    use hawk::Header;
+    use rocket::fairing::AdHoc;
+    use rocket::http::{self, Method, Status};
+    use rocket::local::Client;
+    use rocket::request::{self, FromRequest, Request};
+    use rocket::response::status;
+    use rocket::{Outcome, Rocket};
+    use time::{Duration, Timespec};
+
+    const HEADER: &str = "id=\"xyz\", ts=\"1353832234\", nonce=\"abc\", mac=\"6R4rV5iE+NPoym+WwjeHzjAGXUtLNIxmo1vpMofpLAE=\"";
+
+    // A validator that accepts any header with id "xyz", without checking the MAC
+    struct XyzValidator;
+
+    impl HawkValidator for XyzValidator {
+        type Principal = String;
+
+        fn validate(
+            &self,
+            header: &Header,
+            request: &HawkRequestInfo,
+        ) -> Result<String, (Status, HawkError)> {
+            match header.id {
+                Some(ref id) if id == "xyz" => Ok(format!("{}@{}", id, request.host())),
+                _ => Err((Status::Forbidden, HawkError::UnknownId)),
+            }
+        }
+    }
+
+    #[get("/")]
+    fn index(hawk: Result<Hawk<XyzValidator>, HawkError>) -> status::Custom<String> {
+        match hawk {
+            Ok(principal) => status::Custom(Status::Ok, principal.to_string()),
+            Err(e) => status::Custom(Status::Unauthorized, format!("{:?}", e)),
+        }
+    }
+
+    fn check(rocket: Rocket, header: &str, expected: (Status, &str)) {
+        let client = Client::new(rocket).unwrap();
+        let mut res = client
+            .get("/")
+            .header(http::Header::new("Host", "example.com"))
+            .header(http::Header::new(
+                "Authorization",
+                format!("Hawk {}", header),
+            ))
+            .dispatch();
+        assert_eq!(
+            (res.status(), res.body_string()),
+            (expected.0, Some(expected.1.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_principal() {
+        let rocket = rocket::ignite()
+            .manage(XyzValidator)
+            .manage(HawkConfig::default().ts_skew(Duration::weeks(5200)))
+            .mount("/", routes![index]);
+        check(rocket, HEADER, (Status::Ok, "xyz@example.com"));
+    }
+
+    #[test]
+    fn test_stale_timestamp() {
+        // the HEADER timestamp is from 2012
+        let rocket = rocket::ignite()
+            .manage(XyzValidator)
+            .mount("/", routes![index]);
+        check(rocket, HEADER, (Status::Unauthorized, "StaleTimestamp"));
+    }
+
+    #[test]
+    fn test_required_field() {
+        let rocket = rocket::ignite()
+            .manage(XyzValidator)
+            .manage(HawkConfig::default().require(HawkField::Ext))
+            .mount("/", routes![index]);
+        check(rocket, HEADER, (Status::Unauthorized, "MissingField(Ext)"));
+    }
+
+    #[test]
+    fn test_required_field_other_method() {
+        let config = HawkConfig::default()
+            .ts_skew(Duration::weeks(5200))
+            .require_for(Method::Post, HawkField::Hash);
+        let rocket = rocket::ignite()
+            .manage(XyzValidator)
+            .manage(config)
+            .mount("/", routes![index]);
+        check(rocket, HEADER, (Status::Ok, "xyz@example.com"));
+    }
+
+    #[test]
+    fn test_required_field_present() {
+        let config = HawkConfig::default()
+            .ts_skew(Duration::weeks(5200))
+            .require(HawkField::Ext);
+        let rocket = rocket::ignite()
+            .manage(XyzValidator)
+            .manage(config)
+            .mount("/", routes![index]);
+        let header = format!("{}, ext=\"data\"", HEADER);
+        check(rocket, &header, (Status::Ok, "xyz@example.com"));
+    }
+
+    #[test]
+    fn test_format_limits() {
+        let limited = |config: HawkConfig| {
+            rocket::ignite()
+                .manage(XyzValidator)
+                .manage(config.ts_skew(Duration::weeks(5200)))
+                .mount("/", routes![index])
+        };
+        check(
+            limited(HawkConfig::default().max_ts_digits(9)),
+            HEADER,
+            (Status::Unauthorized, "BadTimestampFormat"),
+        );
+        check(
+            limited(HawkConfig::default().max_nonce_len(2)),
+            HEADER,
+            (Status::Unauthorized, "BadNonceFormat"),
+        );
+        check(
+            limited(HawkConfig::default().nonce_charset("0123456789")),
+            HEADER,
+            (Status::Unauthorized, "BadNonceFormat"),
+        );
+        let config = HawkConfig::default()
+            .max_ts_digits(10)
+            .max_nonce_len(3)
+            .nonce_charset("abc");
+        check(limited(config), HEADER, (Status::Ok, "xyz@example.com"));
+    }
+
+    #[test]
+    fn test_require_payload_hash() {
+        #[post("/")]
+        fn create(hawk: Result<Hawk<XyzValidator>, HawkError>) -> status::Custom<String> {
+            index(hawk)
+        }
+
+        let config = HawkConfig::default()
+            .ts_skew(Duration::weeks(5200))
+            .require_payload_hash(true);
+        let rocket = rocket::ignite()
+            .manage(XyzValidator)
+            .manage(config)
+            .mount("/", routes![index, create]);
+        let client = Client::new(rocket).unwrap();
+        let post = |header: &str| {
+            let mut res = client
+                .post("/")
+                .header(http::Header::new("Host", "example.com"))
+                .header(http::Header::new(
+                    "Authorization",
+                    format!("Hawk {}", header),
+                ))
+                .dispatch();
+            res.body_string().unwrap()
+        };
+
+        assert_eq!(post(HEADER), "MissingPayloadHash");
+        let header = format!(
+            "{}, hash=\"Yi9LfIIFRtBEPt74PVmbTF/xVAwPn7ub15ePICfgnuY=\"",
+            HEADER
+        );
+        assert_eq!(post(&header), "xyz@example.com");
+
+        // requests without a body are unaffected
+        let mut res = client
+            .get("/")
+            .header(http::Header::new("Host", "example.com"))
+            .header(http::Header::new(
+                "Authorization",
+                format!("Hawk {}", HEADER),
+            ))
+            .dispatch();
+        assert_eq!(res.body_string(), Some("xyz@example.com".to_string()));
+    }
+
+    #[test]
+    fn test_fixed_clock() {
+        let config = HawkConfig::default().clock(FixedClock(Timespec::new(1353832250, 0)));
+        let rocket = rocket::ignite()
+            .manage(XyzValidator)
+            .manage(config)
+            .mount("/", routes![index]);
+        check(rocket, HEADER, (Status::Ok, "xyz@example.com"));
+    }
+
+    #[test]
+    fn test_fixed_clock_stale() {
+        let config = HawkConfig::default().clock(FixedClock(Timespec::new(1353832334, 0)));
+        let rocket = rocket::ignite()
+            .manage(XyzValidator)
+            .manage(config)
+            .mount("/", routes![index]);
+        check(rocket, HEADER, (Status::Unauthorized, "StaleTimestamp"));
+    }
+
+    #[test]
+    fn test_extreme_timestamp() {
+        let config = || HawkConfig::default().clock(FixedClock(Timespec::new(1353832250, 0)));
+        for ts in &[i64::MIN, i64::MAX] {
+            let header = HEADER.replace("1353832234", &ts.to_string());
+            let rocket = rocket::ignite()
+                .manage(XyzValidator)
+                .manage(config())
+                .mount("/", routes![index]);
+            check(rocket, &header, (Status::Unauthorized, "StaleTimestamp"));
+        }
+    }
+
+    #[test]
+    fn test_nonce_retention() {
+        let store = RecordingNonceStore::new();
+        let config = |now| {
+            HawkConfig::default()
+                .ts_skew(Duration::seconds(30))
+                .clock(FixedClock(Timespec::new(now, 0)))
+                .nonce_store(store.clone())
+        };
+        let rocket = rocket::ignite()
+            .manage(XyzValidator)
+            .manage(config(1353832250))
+            .mount("/", routes![index]);
+        check(rocket, HEADER, (Status::Ok, "xyz@example.com"));
+
+        // stale requests are rejected without consulting the store
+        let rocket = rocket::ignite()
+            .manage(XyzValidator)
+            .manage(config(1353832334))
+            .mount("/", routes![index]);
+        check(rocket, HEADER, (Status::Unauthorized, "StaleTimestamp"));
+
+        let retained: Vec<_> = store.checks().iter().map(|c| c.retain).collect();
+        assert_eq!(retained, vec![std::time::Duration::from_secs(60)]);
+    }
+
+    #[test]
+    fn test_nonce_store_unavailable() {
+        let store = RecordingNonceStore::new();
+        let config = HawkConfig::default()
+            .clock(FixedClock(Timespec::new(1353832250, 0)))
+            .nonce_store(store.clone());
+        let client = Client::new(
+            rocket::ignite()
+                .manage(XyzValidator)
+                .manage(config)
+                .mount("/", routes![index]),
+        )
+        .unwrap();
+        let get = || {
+            client
+                .get("/")
+                .header(http::Header::new("Host", "example.com"))
+                .header(http::Header::new(
+                    "Authorization",
+                    format!("Hawk {}", HEADER),
+                ))
+                .dispatch()
+                .body_string()
+                .unwrap()
+        };
+
+        // a failing store is not mistaken for a replay, nor does it use up the nonce
+        store.fail("connection refused");
+        assert_eq!(get(), "NonceStoreUnavailable");
+        let config = client.rocket().state::<HawkConfig>().unwrap();
+        assert_eq!(config.replay_count(), 0);
+        assert_eq!(get(), "xyz@example.com");
+        assert_eq!(get(), "ReplayedNonce");
+    }
+
+    #[test]
+    fn test_verify_in_fairing() {
+        // a fairing that verifies the request and records the result in a header
+        let fairing = AdHoc::on_request("verify", |request, _| {
+            let result = match request.guard::<AuthorizationHeader>() {
+                Outcome::Success(header) => {
+                    match verify_rocket_request(request, &header, &XyzValidator) {
+                        Ok(principal) => principal,
+                        Err((_, e)) => format!("{:?}", e),
+                    }
+                }
+                _ => "no header".to_string(),
+            };
+            request.add_header(http::Header::new("X-Hawk-Result", result));
+        });
+
+        #[get("/")]
+        fn result(result: HawkResult) -> String {
+            result.0
+        }
+
+        let rocket = rocket::ignite()
+            .manage(HawkConfig::default().clock(FixedClock(Timespec::new(1353832234, 0))))
+            .attach(fairing)
+            .mount("/", routes![result]);
+        let client = Client::new(rocket).unwrap();
+        let mut res = client
+            .get("/")
+            .header(http::Header::new("Host", "example.com"))
+            .header(http::Header::new(
+                "Authorization",
+                format!("Hawk {}", HEADER),
+            ))
+            .dispatch();
+        assert_eq!(res.body_string(), Some("xyz@example.com".to_string()));
+    }
+
+    // a guard to extract the X-Hawk-Result header set by the fairing in test_verify_in_fairing
+    struct HawkResult(String);
+
+    impl<'a, 'r> FromRequest<'a, 'r> for HawkResult {
+        type Error = ();
+
+        fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, ()> {
+            let result = request.headers().get_one("x-hawk-result").unwrap_or("");
+            Outcome::Success(HawkResult(result.to_string()))
+        }
+    }
+
+    #[test]
+    fn test_rejected() {
+        let rocket = rocket::ignite()
+            .manage(XyzValidator)
+            .mount("/", routes![index]);
+        let header = HEADER.replace("xyz", "abc");
+        check(rocket, &header, (Status::Unauthorized, "UnknownId"));
+    }
+
+    #[test]
+    fn test_no_validator() {
+        let rocket = rocket::ignite().mount("/", routes![index]);
+        check(rocket, HEADER, (Status::Unauthorized, "NoValidator"));
+    }
+}
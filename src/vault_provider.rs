@@ -0,0 +1,349 @@
+use super::provider::credential_from_value;
+use super::{CredentialsProvider, HawkCredential, ProviderError};
+use hyper::header::Headers;
+use hyper::status::StatusCode;
+use hyper::Client;
+use rocket::config::Value;
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A credentials provider that reads keys from a HashiCorp Vault KV secrets engine, so that
+/// keys are never stored in configuration or on disk.  This is available with the `vault`
+/// feature.
+///
+/// The secret for each id is read from `<mount>/data/<prefix><id>` (or `<mount>/<prefix><id>`
+/// for version 1 of the KV engine), and has a base64-encoded `key`, an optional `algorithm`,
+/// and optional `scopes`, as for `MapProvider::from_rocket_config`.  Ids containing characters
+/// other than letters, digits, `-`, `_`, and `.` are treated as unknown.
+///
+/// Credentials are cached for the secret's lease duration, if Vault gives one, or otherwise
+/// for the configured time-to-live.  Unknown ids are not cached.  On Vault errors and invalid
+/// secrets, if the id's cached credential expired less than a time-to-live ago, it remains in
+/// use, and otherwise the lookup fails with a `ProviderError`.  Entries older than that are
+/// removed from the cache as other ids are fetched.
+///
+/// Connections are made over plain HTTP, so the address must be that of a local Vault Agent,
+/// which handles TLS and authentication to the Vault server; other addresses are rejected, so
+/// that tokens and keys never cross the network in cleartext.
+pub struct VaultProvider {
+    address: String,
+    mount: String,
+    prefix: String,
+    token: Option<String>,
+    kv_v1: bool,
+    ttl: Duration,
+    client: Client,
+    cache: Mutex<HashMap<String, (Instant, HawkCredential)>>,
+}
+
+impl VaultProvider {
+    /// Create a provider reading secrets from the KV engine at the given mount, such as
+    /// `secret`, via the Vault Agent at the given address, such as `http://127.0.0.1:8200`.
+    ///
+    /// Returns an error if the address is not an `http://` address on the loopback interface,
+    /// as there is no support for TLS.
+    pub fn new<A: Into<String>, M: Into<String>>(address: A, mount: M) -> Result<Self, String> {
+        let address = address.into();
+        check_address(&address)?;
+        let mut client = Client::new();
+        client.set_read_timeout(Some(Duration::from_secs(5)));
+        client.set_write_timeout(Some(Duration::from_secs(5)));
+        Ok(VaultProvider {
+            address: address.trim_end_matches('/').to_string(),
+            mount: mount.into().trim_matches('/').to_string(),
+            prefix: String::new(),
+            token: None,
+            kv_v1: false,
+            ttl: Duration::from_secs(300),
+            client,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Set a prefix for the path of each id's secret, such as `hawk/`.
+    ///
+    /// The default is no prefix.
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Set the token sent with each request, in the `X-Vault-Token` header.
+    ///
+    /// The default is to send no token, relying on the Vault Agent's auto-auth token.
+    pub fn token<S: Into<String>>(mut self, token: S) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Read secrets from version 1 of the KV engine, rather than version 2.
+    pub fn kv_v1(mut self) -> Self {
+        self.kv_v1 = true;
+        self
+    }
+
+    /// Set the time-to-live of cached credentials whose secrets have no lease duration.
+    ///
+    /// The default is 5 minutes.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    // Get the URL of the secret for the given id.
+    fn url(&self, id: &str) -> String {
+        if self.kv_v1 {
+            format!("{}/v1/{}/{}{}", self.address, self.mount, self.prefix, id)
+        } else {
+            format!(
+                "{}/v1/{}/data/{}{}",
+                self.address, self.mount, self.prefix, id
+            )
+        }
+    }
+
+    // Fetch the credential for the given id, with its time-to-live.
+    fn fetch(&self, id: &str) -> Result<Option<(HawkCredential, Duration)>, String> {
+        let mut headers = Headers::new();
+        if let Some(ref token) = self.token {
+            headers.set_raw("X-Vault-Token", vec![token.clone().into_bytes()]);
+        }
+        let mut res = self
+            .client
+            .get(&self.url(id))
+            .headers(headers)
+            .send()
+            .map_err(|e| e.to_string())?;
+        match res.status {
+            StatusCode::Ok => {}
+            StatusCode::NotFound => return Ok(None),
+            status => return Err(format!("unexpected status {}", status)),
+        }
+        let mut body = String::new();
+        res.read_to_string(&mut body).map_err(|e| e.to_string())?;
+        parse_secret(&body, self.kv_v1, self.ttl).map(Some)
+    }
+}
+
+impl CredentialsProvider for VaultProvider {
+    fn lookup(&self, id: &str) -> Result<Option<HawkCredential>, ProviderError> {
+        if !valid_id(id) {
+            return Ok(None);
+        }
+        let cached = self.cache.lock().unwrap().get(id).cloned();
+        if let Some((expires, ref credential)) = cached {
+            if Instant::now() < expires {
+                return Ok(Some(credential.clone()));
+            }
+        }
+
+        // the lock is not held during the fetch, so that a slow fetch does not block others
+        let fetched = self.fetch(id);
+        let now = Instant::now();
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, (expires, _)| now < *expires + self.ttl);
+        match fetched {
+            Ok(Some((credential, ttl))) => {
+                cache.insert(id.to_string(), (now + ttl, credential.clone()));
+                Ok(Some(credential))
+            }
+            Ok(None) => {
+                cache.remove(id);
+                Ok(None)
+            }
+            // a recently-expired credential is still used while Vault is unavailable
+            Err(e) => match cache.get(id) {
+                Some((_, credential)) => {
+                    rocket::logger::error(&format!(
+                        "Could not read Hawk credentials for {} from Vault, using cached \
+                         credentials: {}",
+                        id, e
+                    ));
+                    Ok(Some(credential.clone()))
+                }
+                None => Err(ProviderError(format!(
+                    "could not read credentials for {} from Vault: {}",
+                    id, e
+                ))),
+            },
+        }
+    }
+}
+
+// Check that an address is a plain HTTP address on the loopback interface, such as that of a
+// local Vault Agent.
+fn check_address(address: &str) -> Result<(), String> {
+    let authority = match address.strip_prefix("http://") {
+        Some(rest) => rest.split('/').next().unwrap_or(""),
+        None if address.starts_with("https://") => {
+            return Err(format!(
+                "Vault address {}: https is not supported; use a local Vault Agent",
+                address
+            ))
+        }
+        None => return Err(format!("Vault address {}: expected http://", address)),
+    };
+    let host = match authority.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(""),
+        None => authority.split(':').next().unwrap_or(""),
+    };
+    let loopback = host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().map_or(false, |ip| ip.is_loopback());
+    if !loopback {
+        return Err(format!(
+            "Vault address {}: plain HTTP is only allowed to a loopback address",
+            address
+        ));
+    }
+    Ok(())
+}
+
+// Check that an id is safe to include in a secret's path.
+fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id != "."
+        && id != ".."
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+// Parse a Vault response into a credential and its time-to-live, using the lease duration if
+// one is given and the default time-to-live otherwise.
+fn parse_secret(
+    body: &str,
+    kv_v1: bool,
+    default_ttl: Duration,
+) -> Result<(HawkCredential, Duration), String> {
+    let response: Value = serde_json::from_str(body).map_err(|e| e.to_string())?;
+    let mut data = response.get("data");
+    if !kv_v1 {
+        data = data.and_then(|d| d.get("data"));
+    }
+    let mut data = data
+        .and_then(|d| d.as_table())
+        .ok_or("expected a data table")?
+        .clone();
+    // a `ttl` in a KV v1 secret sets its lease duration, and is not part of the credential
+    data.remove("ttl");
+    let credential = credential_from_value(&Value::Table(data))?;
+    let ttl = match response.get("lease_duration").and_then(|l| l.as_integer()) {
+        Some(seconds) if seconds > 0 => Duration::from_secs(seconds as u64),
+        _ => default_ttl,
+    };
+    Ok((credential, ttl))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_address, parse_secret, valid_id, VaultProvider};
+    use crate::{CredentialsProvider, HawkAlgorithm};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    const TTL: Duration = Duration::from_secs(300);
+
+    #[test]
+    fn test_valid_id() {
+        assert!(valid_id("me"));
+        assert!(valid_id("client-1.example_2"));
+        assert!(!valid_id(""));
+        assert!(!valid_id(".."));
+        assert!(!valid_id("../other"));
+        assert!(!valid_id("a/b"));
+        assert!(!valid_id("a?b"));
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        let body = r#"{"lease_duration": 0, "data": {"data": {"key": "AQEB", "algorithm": "sha384"},
+            "metadata": {"version": 1}}}"#;
+        let (credential, ttl) = parse_secret(body, false, TTL).unwrap();
+        assert_eq!(credential.algorithm, Some(HawkAlgorithm::Sha384));
+        assert_eq!(ttl, TTL);
+    }
+
+    #[test]
+    fn test_parse_v1() {
+        let body = r#"{"lease_duration": 60, "data": {"key": "AQEB", "ttl": "1m"}}"#;
+        let (credential, ttl) = parse_secret(body, true, TTL).unwrap();
+        assert_eq!(credential.algorithm, Some(HawkAlgorithm::Sha256));
+        assert_eq!(ttl, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse_secret("{", false, TTL).is_err());
+        assert!(parse_secret(r#"{"data": {"key": "AQEB"}}"#, false, TTL).is_err());
+        assert!(parse_secret(r#"{"data": {"data": {"key": "!!"}}}"#, false, TTL).is_err());
+    }
+
+    #[test]
+    fn test_lookup() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        // serve a single request, returning the request
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0u8; 4096];
+            let len = stream.read(&mut request).unwrap();
+            let body = r#"{"data": {"data": {"key": "AQEB"}}}"#;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            String::from_utf8_lossy(&request[..len]).to_string()
+        });
+
+        let provider = VaultProvider::new(address, "secret")
+            .unwrap()
+            .prefix("hawk/")
+            .token("t0ken");
+        assert!(provider.lookup("me").unwrap().is_some());
+        // the second lookup is served from the cache
+        assert!(provider.lookup("me").unwrap().is_some());
+        assert!(provider.lookup("../me").unwrap().is_none());
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("GET /v1/secret/data/hawk/me HTTP/1.1\r\n"));
+        assert!(request.contains("X-Vault-Token: t0ken\r\n"));
+
+        // the server has stopped, so an expired credential is used until it is a ttl old
+        let expire = |age: Duration| {
+            let mut cache = provider.cache.lock().unwrap();
+            cache.get_mut("me").unwrap().0 = Instant::now() - age;
+        };
+        expire(Duration::from_secs(1));
+        assert!(provider.lookup("me").unwrap().is_some());
+        expire(TTL + Duration::from_secs(1));
+        assert!(provider.lookup("me").is_err());
+        assert!(provider.cache.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_check_address() {
+        assert!(check_address("http://127.0.0.1:8200").is_ok());
+        assert!(check_address("http://localhost:8200/").is_ok());
+        assert!(check_address("http://[::1]:8200").is_ok());
+        assert!(check_address("http://vault.example.com:8200").is_err());
+        assert!(check_address("http://10.0.0.1").is_err());
+        assert_eq!(
+            check_address("https://vault.example.com").err(),
+            Some(
+                "Vault address https://vault.example.com: https is not supported; \
+                 use a local Vault Agent"
+                    .to_string()
+            )
+        );
+        assert!(check_address("vault:8200").is_err());
+        assert!(VaultProvider::new("https://127.0.0.1:8200", "secret").is_err());
+    }
+}